@@ -0,0 +1,213 @@
+//! Swept AABB collision against a grid of tile shapes, resolved one axis at a time.
+use macroquad::math::{Rect, Vec2, vec2};
+
+/// The solid part of a tile. Slopes are named after the corner that is solid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileShape {
+    #[default]
+    Empty,
+    Full,
+    HalfTop,
+    HalfBottom,
+    HalfLeft,
+    HalfRight,
+    SlopeTopLeft,
+    SlopeTopRight,
+    SlopeBottomLeft,
+    SlopeBottomRight,
+}
+impl TileShape {
+    /// Parses the `shape` tile property.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "empty" => Self::Empty,
+            "full" => Self::Full,
+            "half_top" => Self::HalfTop,
+            "half_bottom" => Self::HalfBottom,
+            "half_left" => Self::HalfLeft,
+            "half_right" => Self::HalfRight,
+            "slope_top_left" => Self::SlopeTopLeft,
+            "slope_top_right" => Self::SlopeTopRight,
+            "slope_bottom_left" => Self::SlopeBottomLeft,
+            "slope_bottom_right" => Self::SlopeBottomRight,
+            _ => return None,
+        })
+    }
+    /// Whether a point within a unit tile lies in the solid area.
+    pub fn contains(self, x: f32, y: f32) -> bool {
+        match self {
+            Self::Empty => false,
+            Self::Full => true,
+            Self::HalfTop => y < 0.5,
+            Self::HalfBottom => y >= 0.5,
+            Self::HalfLeft => x < 0.5,
+            Self::HalfRight => x >= 0.5,
+            Self::SlopeTopLeft => x + y < 1.0,
+            Self::SlopeTopRight => y < x,
+            Self::SlopeBottomLeft => y > x,
+            Self::SlopeBottomRight => x + y > 1.0,
+        }
+    }
+    /// Corners of the solid area within a unit tile, as a convex polygon.
+    fn polygon(self) -> &'static [(f32, f32)] {
+        match self {
+            Self::Empty => &[],
+            Self::Full => &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            Self::HalfTop => &[(0.0, 0.0), (1.0, 0.0), (1.0, 0.5), (0.0, 0.5)],
+            Self::HalfBottom => &[(0.0, 0.5), (1.0, 0.5), (1.0, 1.0), (0.0, 1.0)],
+            Self::HalfLeft => &[(0.0, 0.0), (0.5, 0.0), (0.5, 1.0), (0.0, 1.0)],
+            Self::HalfRight => &[(0.5, 0.0), (1.0, 0.0), (1.0, 1.0), (0.5, 1.0)],
+            Self::SlopeTopLeft => &[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            Self::SlopeTopRight => &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            Self::SlopeBottomLeft => &[(0.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            Self::SlopeBottomRight => &[(1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+        }
+    }
+}
+
+/// Which sides of the box were stopped by a tile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hit {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+}
+impl Hit {
+    pub fn horizontal(&self) -> bool {
+        self.left || self.right
+    }
+    pub fn vertical(&self) -> bool {
+        self.top || self.bottom
+    }
+}
+
+/// Touching a tile is not a collision, only overlapping it by more than this is.
+const EPSILON: f32 = 0.001;
+
+/// Moves `aabb` by `motion`, first along x and then along y, stopping at the first
+/// solid edge on each axis. `shape_at` gives the shape of the tile at a grid position.
+/// Returns how far the box actually moved. A box that already overlaps a tile is free
+/// to move out of it.
+pub fn move_aabb(
+    aabb: Rect,
+    motion: Vec2,
+    tile_size: f32,
+    shape_at: impl Fn(i32, i32) -> TileShape,
+) -> (Vec2, Hit) {
+    let mut hit = Hit::default();
+    let mut aabb = aabb;
+    let dx = sweep(aabb, motion.x, tile_size, &shape_at, Axis::X);
+    if dx != motion.x {
+        hit.left = motion.x < 0.0;
+        hit.right = motion.x > 0.0;
+    }
+    aabb.x += dx;
+    let dy = sweep(aabb, motion.y, tile_size, &shape_at, Axis::Y);
+    if dy != motion.y {
+        hit.top = motion.y < 0.0;
+        hit.bottom = motion.y > 0.0;
+    }
+    (vec2(dx, dy), hit)
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    X,
+    Y,
+}
+
+/// How far `aabb` can travel `distance` along `axis` before touching a solid edge.
+fn sweep(
+    aabb: Rect,
+    distance: f32,
+    tile_size: f32,
+    shape_at: &impl Fn(i32, i32) -> TileShape,
+    axis: Axis,
+) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+    // Swap coordinates so the sweep always runs along the first component.
+    let swap = |p: (f32, f32)| match axis {
+        Axis::X => p,
+        Axis::Y => (p.1, p.0),
+    };
+    let ((start, end), (cross_start, cross_end)) = match axis {
+        Axis::X => ((aabb.left(), aabb.right()), (aabb.top(), aabb.bottom())),
+        Axis::Y => ((aabb.top(), aabb.bottom()), (aabb.left(), aabb.right())),
+    };
+    let (swept_start, swept_end) = if distance > 0.0 {
+        (end, end + distance)
+    } else {
+        (start + distance, start)
+    };
+    let first = |value: f32| (value / tile_size).floor() as i32;
+    let last = |value: f32| (value / tile_size).ceil() as i32 - 1;
+    let cross = first(cross_start + EPSILON)..=last(cross_end - EPSILON);
+    let along = first(swept_start - EPSILON)..=last(swept_end + EPSILON);
+
+    let mut allowed = distance;
+    for a in along {
+        for c in cross.clone() {
+            let (tx, ty) = swap((a as f32, c as f32));
+            let shape = shape_at(tx as i32, ty as i32);
+            let points: Vec<(f32, f32)> = shape
+                .polygon()
+                .iter()
+                .map(|p| swap(((tx + p.0) * tile_size, (ty + p.1) * tile_size)))
+                .collect();
+            let clipped = clip_slab(&points, cross_start + EPSILON, cross_end - EPSILON);
+            if clipped.is_empty() {
+                continue;
+            }
+            if distance > 0.0 {
+                let edge = clipped.iter().map(|p| p.0).fold(f32::INFINITY, f32::min);
+                if edge >= end - EPSILON {
+                    allowed = allowed.min(edge - end);
+                }
+            } else {
+                let edge = clipped
+                    .iter()
+                    .map(|p| p.0)
+                    .fold(f32::NEG_INFINITY, f32::max);
+                if edge <= start + EPSILON {
+                    allowed = allowed.max(edge - start);
+                }
+            }
+        }
+    }
+    if allowed != distance && allowed.abs() < EPSILON {
+        0.0
+    } else {
+        allowed
+    }
+}
+
+/// Clips a convex polygon to `min <= y <= max`.
+fn clip_slab(points: &[(f32, f32)], min: f32, max: f32) -> Vec<(f32, f32)> {
+    let clipped = clip_half_plane(points, |p| p.1 - min);
+    clip_half_plane(&clipped, |p| max - p.1)
+}
+
+/// Keeps the part of a convex polygon where `distance` is not negative.
+fn clip_half_plane(points: &[(f32, f32)], distance: impl Fn((f32, f32)) -> f32) -> Vec<(f32, f32)> {
+    let mut out = Vec::with_capacity(points.len() + 1);
+    for (i, current) in points.iter().copied().enumerate() {
+        let next = points[(i + 1) % points.len()];
+        let (d0, d1) = (distance(current), distance(next));
+        if d0 >= 0.0 {
+            out.push(current);
+        }
+        if (d0 >= 0.0) != (d1 >= 0.0) {
+            let t = d0 / (d0 - d1);
+            out.push((
+                current.0 + (next.0 - current.0) * t,
+                current.1 + (next.1 - current.1) * t,
+            ));
+        }
+    }
+    out
+}
+#[cfg(test)]
+mod tests;
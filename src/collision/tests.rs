@@ -0,0 +1,170 @@
+//! Boxes swept against small hand-built grids of 16 pixel tiles.
+use super::*;
+
+const TILE: f32 = 16.0;
+
+/// A grid that is empty apart from `tiles`.
+fn grid(tiles: &[(i32, i32, TileShape)]) -> impl Fn(i32, i32) -> TileShape + '_ {
+    |x, y| {
+        tiles
+            .iter()
+            .find(|f| (f.0, f.1) == (x, y))
+            .map_or(TileShape::Empty, |f| f.2)
+    }
+}
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 0.01
+}
+
+#[test]
+fn boxes_slide_along_walls() {
+    let floor: Vec<_> = (-5..5).map(|x| (x, 1, TileShape::Full)).collect();
+    // Resting on the floor and pushing into it while moving right.
+    let (moved, hit) = move_aabb(
+        Rect::new(0.0, 8.0, 8.0, 8.0),
+        vec2(10.0, 5.0),
+        TILE,
+        grid(&floor),
+    );
+    assert_eq!(moved, vec2(10.0, 0.0));
+    assert_eq!(
+        hit,
+        Hit {
+            bottom: true,
+            ..Default::default()
+        }
+    );
+
+    let wall: Vec<_> = (-5..5).map(|y| (1, y, TileShape::Full)).collect();
+    let (moved, hit) = move_aabb(
+        Rect::new(8.0, 0.0, 8.0, 8.0),
+        vec2(3.0, -7.0),
+        TILE,
+        grid(&wall),
+    );
+    assert_eq!(moved, vec2(0.0, -7.0));
+    assert!(hit.right && !hit.vertical());
+}
+
+#[test]
+fn boxes_stop_flush_against_walls() {
+    let tiles = [(2, 0, TileShape::Full)];
+    let aabb = Rect::new(0.0, 0.0, 8.0, 8.0);
+    let (moved, hit) = move_aabb(aabb, vec2(40.0, 0.0), TILE, grid(&tiles));
+    assert_eq!(moved, vec2(24.0, 0.0));
+    assert!(hit.right);
+
+    // Touching the wall is not overlapping it, so the box can still move away or along it.
+    let flush = Rect::new(24.0, 0.0, 8.0, 8.0);
+    let (moved, hit) = move_aabb(flush, vec2(1.0, 0.0), TILE, grid(&tiles));
+    assert_eq!((moved, hit.right), (Vec2::ZERO, true));
+    let (moved, hit) = move_aabb(flush, vec2(-1.0, 4.0), TILE, grid(&tiles));
+    assert_eq!((moved, hit), (vec2(-1.0, 4.0), Hit::default()));
+}
+
+#[test]
+fn fast_boxes_do_not_tunnel_through_thin_walls() {
+    let tiles = [(2, 0, TileShape::Full)];
+    let aabb = Rect::new(0.0, 0.0, 8.0, 8.0);
+    let (moved, _) = move_aabb(aabb, vec2(1000.0, 0.0), TILE, grid(&tiles));
+    assert_eq!(moved.x, 24.0);
+
+    let aabb = Rect::new(60.0, 0.0, 8.0, 8.0);
+    let (moved, _) = move_aabb(aabb, vec2(-1000.0, 0.0), TILE, grid(&tiles));
+    assert_eq!(moved.x, -12.0);
+
+    let tiles = [(0, 2, TileShape::HalfTop)];
+    let aabb = Rect::new(4.0, 0.0, 8.0, 8.0);
+    let (moved, hit) = move_aabb(aabb, vec2(0.0, 1000.0), TILE, grid(&tiles));
+    assert_eq!(moved.y, 24.0);
+    assert!(hit.bottom);
+}
+
+#[test]
+fn boxes_already_inside_a_tile_can_leave() {
+    let tiles = [(0, 0, TileShape::Full)];
+    let (moved, hit) = move_aabb(
+        Rect::new(4.0, 4.0, 8.0, 8.0),
+        vec2(20.0, 0.0),
+        TILE,
+        grid(&tiles),
+    );
+    assert_eq!((moved, hit), (vec2(20.0, 0.0), Hit::default()));
+}
+
+#[test]
+fn partial_tiles_stop_boxes_at_their_solid_edge() {
+    use TileShape::*;
+    // Where a 4x4 box running through the lane a quarter of the way into tile (1, 1)
+    // stops, in tile units, coming from the left, right, top and bottom. `None` means it
+    // passes straight through.
+    let cases = [
+        (Empty, [None, None, None, None]),
+        (Full, [Some(0.0), Some(1.0), Some(0.0), Some(1.0)]),
+        (HalfTop, [Some(0.0), Some(1.0), Some(0.0), Some(0.5)]),
+        (HalfBottom, [None, None, Some(0.5), Some(1.0)]),
+        (HalfLeft, [Some(0.0), Some(0.5), Some(0.0), Some(1.0)]),
+        (HalfRight, [Some(0.5), Some(1.0), None, None]),
+        (SlopeTopLeft, [Some(0.0), Some(0.75), Some(0.0), Some(0.75)]),
+        (SlopeTopRight, [Some(0.25), Some(1.0), Some(0.0), Some(0.5)]),
+        (
+            SlopeBottomLeft,
+            [Some(0.0), Some(0.5), Some(0.25), Some(1.0)],
+        ),
+        (
+            SlopeBottomRight,
+            [Some(0.5), Some(1.0), Some(0.5), Some(1.0)],
+        ),
+    ];
+    for (shape, stops) in cases {
+        let tiles = [(1, 1, shape)];
+        let lane = TILE * 1.25;
+        let runs = [
+            (Rect::new(0.0, lane, 4.0, 4.0), vec2(40.0, 0.0)),
+            (Rect::new(44.0, lane, 4.0, 4.0), vec2(-40.0, 0.0)),
+            (Rect::new(lane, 0.0, 4.0, 4.0), vec2(0.0, 40.0)),
+            (Rect::new(lane, 44.0, 4.0, 4.0), vec2(0.0, -40.0)),
+        ];
+        for ((aabb, motion), stop) in runs.into_iter().zip(stops) {
+            let (moved, hit) = move_aabb(aabb, motion, TILE, grid(&tiles));
+            let expected = TILE + stop.unwrap_or(0.0) * TILE;
+            let reached = if motion.x > 0.0 {
+                aabb.right() + moved.x
+            } else if motion.x < 0.0 {
+                aabb.left() + moved.x
+            } else if motion.y > 0.0 {
+                aabb.bottom() + moved.y
+            } else {
+                aabb.top() + moved.y
+            };
+            match stop {
+                Some(_) => {
+                    assert!(
+                        close(reached, expected),
+                        "{shape:?} moving {motion}: stopped at {reached}, not {expected}"
+                    );
+                    assert!(hit.horizontal() || hit.vertical());
+                }
+                None => {
+                    assert_eq!(moved, motion, "{shape:?} moving {motion}");
+                    assert_eq!(hit, Hit::default());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn clipping_keeps_the_part_of_a_polygon_inside_the_slab() {
+    let triangle = TileShape::SlopeTopLeft.polygon();
+    let clipped = clip_slab(triangle, 0.25, 0.5);
+    let xs: Vec<f32> = clipped.iter().map(|f| f.0).collect();
+    let ys: Vec<f32> = clipped.iter().map(|f| f.1).collect();
+    assert!(ys.iter().all(|y| (0.25..=0.5).contains(y)), "{clipped:?}");
+    assert!(close(
+        xs.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        0.75
+    ));
+    assert!(close(xs.iter().copied().fold(f32::INFINITY, f32::min), 0.0));
+    assert!(clip_slab(triangle, 1.5, 2.0).is_empty());
+}
@@ -0,0 +1,58 @@
+//! Files the player keeps outside the game. Native builds read and write them next to
+//! the game; on the web they are downloaded and uploaded through the page.
+
+/// Writes `text` to the file `name`, or downloads it under that name on the web.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(name: &str, text: &str) -> Result<(), String> {
+    std::fs::write(name, text).map_err(|err| err.to_string())
+}
+/// Starts opening the file `name`. On the web the player picks any file instead, and it
+/// arrives some frames later, see `opened`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(name: &str) {
+    let result = std::fs::read_to_string(name).map_err(|err| format!("{name}: {err}"));
+    *native::OPENED.lock().unwrap() = Some(result);
+}
+/// The contents of the file asked for with `open`, once it is there.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn opened() -> Option<Result<String, String>> {
+    native::OPENED.lock().unwrap().take()
+}
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::sync::Mutex;
+    pub static OPENED: Mutex<Option<Result<String, String>>> = Mutex::new(None);
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save(name: &str, text: &str) -> Result<(), String> {
+    unsafe { web::file_download(name.as_ptr(), name.len(), text.as_ptr(), text.len()) };
+    Ok(())
+}
+#[cfg(target_arch = "wasm32")]
+pub fn open(_name: &str) {
+    unsafe { web::file_pick() };
+}
+#[cfg(target_arch = "wasm32")]
+pub fn opened() -> Option<Result<String, String>> {
+    let len = usize::try_from(unsafe { web::file_picked_len() }).ok()?;
+    let mut bytes = vec![0; len];
+    unsafe { web::file_take_picked(bytes.as_mut_ptr()) };
+    Some(String::from_utf8(bytes).map_err(|_| "not a text file".to_string()))
+}
+/// Implemented by the `catscapade_files` plugin in web/index.html.
+#[cfg(target_arch = "wasm32")]
+mod web {
+    unsafe extern "C" {
+        pub fn file_download(name: *const u8, name_len: usize, text: *const u8, text_len: usize);
+        pub fn file_pick();
+        /// Size of the picked file in bytes, or -1 while there is none.
+        pub fn file_picked_len() -> i32;
+        /// Copies the picked file into `buffer` and forgets it.
+        pub fn file_take_picked(buffer: *mut u8);
+    }
+    #[unsafe(no_mangle)]
+    pub extern "C" fn catscapade_files_crate_version() -> u32 {
+        1
+    }
+}
@@ -0,0 +1,54 @@
+//! Path costs over the tile grid. Mice walk downhill on them to get away from the cat.
+use crate::map::Map;
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    /// Cost of every tile, in `map::STEP`s of ten per tile. `None` where it can't be
+    /// reached.
+    costs: Vec<Option<i32>>,
+}
+impl FlowField {
+    /// Path cost from every walkable tile to the nearest of `sources`.
+    pub fn distances(map: &Map, sources: impl IntoIterator<Item = usize>) -> Self {
+        Self::relax(map, sources.into_iter().map(|f| (f, 0)))
+    }
+    /// Where to run from whatever `danger` measures the distance to. Every tile starts at
+    /// its distance times `-flee` and is then relaxed again, so a tile with a way out
+    /// beats a dead end that only happens to be a little farther away.
+    pub fn flee(map: &Map, danger: &FlowField, flee: f32) -> Self {
+        let seeds = danger
+            .costs
+            .iter()
+            .enumerate()
+            .filter_map(|(tile, cost)| Some((tile, -((*cost)? as f32 * flee) as i32)));
+        Self::relax(map, seeds)
+    }
+    /// Dijkstra from tiles that already have a cost.
+    fn relax(map: &Map, seeds: impl Iterator<Item = (usize, i32)>) -> Self {
+        let mut costs = vec![None; map.tiles.len()];
+        let mut queue = BinaryHeap::new();
+        for (tile, cost) in seeds {
+            if map.is_open(tile) && costs[tile].is_none_or(|f| cost < f) {
+                costs[tile] = Some(cost);
+                queue.push(Reverse((cost, tile)));
+            }
+        }
+        while let Some(Reverse((cost, tile))) = queue.pop() {
+            if costs[tile].is_some_and(|f| cost > f) {
+                continue;
+            }
+            for (next, step) in map.neighbours(tile) {
+                let next_cost = cost + step as i32;
+                if costs[next].is_none_or(|f| next_cost < f) {
+                    costs[next] = Some(next_cost);
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+        Self { costs }
+    }
+    pub fn cost(&self, tile: usize) -> Option<i32> {
+        self.costs.get(tile).copied().flatten()
+    }
+}
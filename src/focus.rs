@@ -0,0 +1,18 @@
+//! Noticing when the player switches away, so a running round can pause itself. On the
+//! web the page reports hidden tabs and lost focus. macroquad 0.4 keeps miniquad's
+//! window focus events to itself on desktop, so native builds are never told.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static LOST: AtomicBool = AtomicBool::new(false);
+
+/// Whether focus was lost since the last call.
+pub fn lost() -> bool {
+    LOST.swap(false, Ordering::Relaxed)
+}
+
+/// Called from web/index.html on `blur`, and on `visibilitychange` when the page is hidden.
+#[cfg(target_arch = "wasm32")]
+#[unsafe(no_mangle)]
+pub extern "C" fn catscapade_focus_lost() {
+    LOST.store(true, Ordering::Relaxed);
+}
@@ -0,0 +1,70 @@
+//! The path of a finished run, raced against in later rounds. Unlike a replay it keeps
+//! working after the rules change, since nothing is simulated again.
+use crate::sim::World;
+use macroquad::math::{Vec2, vec2};
+use std::{fmt, str::FromStr};
+
+const HEADER: &str = "catscapade-ghost 1";
+/// Ticks between samples.
+const SAMPLE_TICKS: u32 = 6;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ghost {
+    /// Cat position and kills every `SAMPLE_TICKS` ticks, starting before the first one.
+    samples: Vec<(Vec2, u32)>,
+}
+impl Ghost {
+    /// Samples the world if it is due. Call once before the first tick and after each.
+    pub fn record(&mut self, world: &World) {
+        if world.ticks.is_multiple_of(SAMPLE_TICKS) {
+            self.samples.push((world.cat.pos, world.kills));
+        }
+    }
+    /// Where the cat was and which way it was heading `ticks` ticks into the run, and its
+    /// kills so far. `None` once the run is over.
+    pub fn at(&self, ticks: f32) -> Option<(Vec2, Vec2, u32)> {
+        let sample = ticks.max(0.0) / SAMPLE_TICKS as f32;
+        let index = sample as usize;
+        let from = self.samples.get(index)?;
+        let to = self.samples.get(index + 1).unwrap_or(from);
+        Some((from.0.lerp(to.0, sample.fract()), to.0 - from.0, from.1))
+    }
+}
+impl fmt::Display for Ghost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for sample in self.samples.iter() {
+            writeln!(f, "{} {} {}", sample.0.x, sample.0.y, sample.1)?;
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq)]
+pub struct ParseGhostError;
+impl fmt::Display for ParseGhostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a ghost")
+    }
+}
+impl std::error::Error for ParseGhostError {}
+impl FromStr for Ghost {
+    type Err = ParseGhostError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(ParseGhostError);
+        }
+        let samples = lines
+            .filter(|f| !f.is_empty())
+            .map(|line| {
+                let mut values = line.split(' ');
+                let mut next = || values.next().ok_or(ParseGhostError);
+                let x = next()?.parse().map_err(|_| ParseGhostError)?;
+                let y = next()?.parse().map_err(|_| ParseGhostError)?;
+                let kills = next()?.parse().map_err(|_| ParseGhostError)?;
+                Ok((vec2(x, y), kills))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { samples })
+    }
+}
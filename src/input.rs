@@ -0,0 +1,444 @@
+//! What the player can do, and which keys do it.
+use macroquad::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Pause,
+    Confirm,
+}
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Pause,
+        Action::Confirm,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+        }
+    }
+    /// Used in storage, so it must never change.
+    fn id(self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Pause => "pause",
+            Action::Confirm => "confirm",
+        }
+    }
+}
+
+/// Keys that can be bound. Backspace is left out since it clears a binding.
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpEnter,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Enter,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::LeftShift,
+    KeyCode::RightShift,
+    KeyCode::LeftControl,
+    KeyCode::RightControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightAlt,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::LeftBracket,
+    KeyCode::RightBracket,
+    KeyCode::Backslash,
+    KeyCode::GraveAccent,
+];
+pub fn key_name(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|f| key_name(*f) == name)
+}
+pub fn is_bindable(key: KeyCode) -> bool {
+    BINDABLE_KEYS.contains(&key)
+}
+
+/// Up to two keys per action, indexed like `Action::ALL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    keys: [[Option<KeyCode>; 2]; Action::ALL.len()],
+}
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keys: [
+                [Some(KeyCode::W), Some(KeyCode::Up)],
+                [Some(KeyCode::S), Some(KeyCode::Down)],
+                [Some(KeyCode::A), Some(KeyCode::Left)],
+                [Some(KeyCode::D), Some(KeyCode::Right)],
+                [Some(KeyCode::Escape), Some(KeyCode::P)],
+                [Some(KeyCode::Enter), Some(KeyCode::Space)],
+            ],
+        }
+    }
+}
+impl Bindings {
+    const STORAGE_KEY: &str = "bindings";
+    /// The saved bindings. Actions missing from storage keep their defaults.
+    pub fn load() -> Self {
+        let mut bindings = Self::default();
+        let storage = quad_storage::LocalStorage::default();
+        let Some(saved) = storage.get(Self::STORAGE_KEY) else {
+            return bindings;
+        };
+        for line in saved.lines() {
+            let mut values = line.split(' ');
+            let Some(action) = values
+                .next()
+                .and_then(|id| Action::ALL.into_iter().find(|f| f.id() == id))
+            else {
+                continue;
+            };
+            let slots = &mut bindings.keys[action as usize];
+            for slot in slots.iter_mut() {
+                *slot = values.next().and_then(key_from_name);
+            }
+        }
+        bindings
+    }
+    pub fn save(&self) {
+        let saved: String = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys: Vec<String> = self.keys[action as usize]
+                    .iter()
+                    .map(|f| f.map_or("-".to_string(), key_name))
+                    .collect();
+                format!("{} {}\n", action.id(), keys.join(" "))
+            })
+            .collect();
+        let mut storage = quad_storage::LocalStorage::default();
+        storage.set(Self::STORAGE_KEY, &saved);
+    }
+    pub fn keys(&self, action: Action) -> [Option<KeyCode>; 2] {
+        self.keys[action as usize]
+    }
+    /// Binds `key` to one of an action's slots, or clears the slot. A key only ever does
+    /// one thing, so it is taken away from whatever it was bound to before.
+    pub fn bind(&mut self, action: Action, slot: usize, key: Option<KeyCode>) {
+        if key.is_some() {
+            for slots in self.keys.iter_mut() {
+                for bound in slots.iter_mut() {
+                    if *bound == key {
+                        *bound = None;
+                    }
+                }
+            }
+        }
+        self.keys[action as usize][slot] = key;
+    }
+    pub fn is_down(&self, action: Action) -> bool {
+        self.keys(action).into_iter().flatten().any(is_key_down)
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.keys(action).into_iter().flatten().any(is_key_pressed)
+    }
+}
+
+/// Sticks pushed less than this far count as centred.
+const STICK_DEADZONE: f32 = 0.2;
+
+/// Connected controllers. Without the `gamepad` feature, and on the web, there are none.
+pub struct Gamepads {
+    #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+    gilrs: Option<gilrs::Gilrs>,
+    /// Actions whose button went down since the last `update`.
+    pressed: Vec<Action>,
+}
+impl Gamepads {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+            gilrs: gilrs::Gilrs::new().ok(),
+            pressed: Vec::new(),
+        }
+    }
+    /// Collects button presses. Call once per frame.
+    pub fn update(&mut self) {
+        self.pressed.clear();
+        #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+        if let Some(gilrs) = self.gilrs.as_mut() {
+            while let Some(event) = gilrs.next_event() {
+                if let gilrs::EventType::ButtonPressed(button, _) = event.event
+                    && let Some(action) = Self::action(button)
+                {
+                    self.pressed.push(action);
+                }
+            }
+        }
+    }
+    #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+    fn action(button: gilrs::Button) -> Option<Action> {
+        use gilrs::Button;
+        Some(match button {
+            Button::DPadUp => Action::MoveUp,
+            Button::DPadDown => Action::MoveDown,
+            Button::DPadLeft => Action::MoveLeft,
+            Button::DPadRight => Action::MoveRight,
+            Button::Start => Action::Pause,
+            Button::South => Action::Confirm,
+            _ => return None,
+        })
+    }
+    pub fn is_down(&self, action: Action) -> bool {
+        #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+        if let Some(gilrs) = self.gilrs.as_ref() {
+            return gilrs.gamepads().any(|(_, gamepad)| {
+                [
+                    gilrs::Button::DPadUp,
+                    gilrs::Button::DPadDown,
+                    gilrs::Button::DPadLeft,
+                    gilrs::Button::DPadRight,
+                    gilrs::Button::Start,
+                    gilrs::Button::South,
+                ]
+                .into_iter()
+                .any(|f| Self::action(f) == Some(action) && gamepad.is_pressed(f))
+            });
+        }
+        let _ = action;
+        false
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    /// The left stick with y pointing down, scaled so it grows from zero at the edge of
+    /// the deadzone to one when pushed all the way.
+    pub fn stick(&self) -> Vec2 {
+        #[allow(unused_mut)]
+        let mut stick = Vec2::ZERO;
+        #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+        if let Some(gilrs) = self.gilrs.as_ref() {
+            for (_, gamepad) in gilrs.gamepads() {
+                stick += vec2(
+                    gamepad.value(gilrs::Axis::LeftStickX),
+                    -gamepad.value(gilrs::Axis::LeftStickY),
+                );
+            }
+        }
+        apply_deadzone(stick)
+    }
+}
+
+/// Scales a stick so it grows from zero at the edge of the deadzone to one when pushed
+/// all the way.
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length < STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    stick / length * ((length - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0)
+}
+
+/// How far a finger has to move from where it went down to push the joystick all the way.
+fn joystick_radius() -> f32 {
+    screen_width().min(screen_height()) * 0.12
+}
+/// Where the pause button goes on touch screens, in the bottom right corner. That is
+/// below the game view in portrait and beside it in landscape.
+pub fn pause_button() -> Rect {
+    let size = screen_width().min(screen_height()) * 0.12;
+    let margin = size / 3.0;
+    Rect::new(
+        screen_width() - size - margin,
+        screen_height() - size - margin,
+        size,
+        size,
+    )
+}
+
+/// On-screen controls: a joystick that appears wherever a finger goes down, and buttons
+/// that fire an action when tapped.
+#[derive(Default)]
+pub struct TouchControls {
+    /// The finger steering and where it went down.
+    joystick: Option<(u64, Vec2)>,
+    /// Where the steering finger is now.
+    knob: Vec2,
+    buttons: Vec<(Action, Rect)>,
+    /// Actions whose button was tapped since the last `update`.
+    pressed: Vec<Action>,
+    /// Set after the first touch, so the controls only show up on touch screens.
+    active: bool,
+}
+impl TouchControls {
+    /// Handles this frame's touches, with `buttons` on screen. Call once per frame.
+    pub fn update(&mut self, buttons: Vec<(Action, Rect)>) {
+        self.buttons = buttons;
+        self.pressed.clear();
+        for touch in touches() {
+            self.active = true;
+            let steering = self.joystick.is_some_and(|f| f.0 == touch.id);
+            match touch.phase {
+                TouchPhase::Started => {
+                    if let Some(button) = self.buttons.iter().find(|f| f.1.contains(touch.position))
+                    {
+                        self.pressed.push(button.0);
+                    } else if self.joystick.is_none() {
+                        self.joystick = Some((touch.id, touch.position));
+                        self.knob = touch.position;
+                    }
+                }
+                TouchPhase::Moved | TouchPhase::Stationary if steering => {
+                    self.knob = touch.position;
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled if steering => self.joystick = None,
+                _ => {}
+            }
+        }
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    /// The joystick with y pointing down, from zero to one in length.
+    pub fn stick(&self) -> Vec2 {
+        let Some((_, origin)) = self.joystick else {
+            return Vec2::ZERO;
+        };
+        apply_deadzone(((self.knob - origin) / joystick_radius()).clamp_length_max(1.0))
+    }
+    /// Draws the joystick and buttons in screen space, once the player has touched the
+    /// screen.
+    pub fn draw(&self) {
+        if !self.active {
+            return;
+        }
+        let color = WHITE.with_alpha(0.4);
+        let radius = joystick_radius();
+        if let Some((_, origin)) = self.joystick {
+            let knob = origin + (self.knob - origin).clamp_length_max(radius);
+            draw_circle_lines(origin.x, origin.y, radius, 3.0, color);
+            draw_circle(knob.x, knob.y, radius / 2.5, color);
+        }
+        for (action, rect) in self.buttons.iter() {
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 3.0, color);
+            if *action == Action::Pause {
+                let bar = Rect::new(
+                    rect.x + rect.w * 0.3,
+                    rect.y + rect.h * 0.25,
+                    rect.w * 0.12,
+                    rect.h * 0.5,
+                );
+                draw_rectangle(bar.x, bar.y, bar.w, bar.h, color);
+                draw_rectangle(bar.x + rect.w * 0.28, bar.y, bar.w, bar.h, color);
+            }
+        }
+    }
+}
+
+/// Every way of triggering an action: the key bindings, any controllers and the touch
+/// screen.
+pub struct Controls {
+    pub bindings: Bindings,
+    pub gamepads: Gamepads,
+    pub touch: TouchControls,
+}
+impl Controls {
+    pub fn load() -> Self {
+        Self {
+            bindings: Bindings::load(),
+            gamepads: Gamepads::new(),
+            touch: TouchControls::default(),
+        }
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.bindings.is_pressed(action)
+            || self.gamepads.is_pressed(action)
+            || self.touch.is_pressed(action)
+    }
+    /// How far and which way to move, with each axis from -1 to 1. The sticks are analog,
+    /// keys and the d-pad are all or nothing.
+    pub fn movement(&self) -> Vec2 {
+        let axis = |negative, positive| {
+            let down = |action| self.bindings.is_down(action) || self.gamepads.is_down(action);
+            down(positive) as i32 as f32 - down(negative) as i32 as f32
+        };
+        let digital = vec2(
+            axis(Action::MoveLeft, Action::MoveRight),
+            axis(Action::MoveUp, Action::MoveDown),
+        );
+        if digital != Vec2::ZERO {
+            return digital;
+        }
+        let stick = self.gamepads.stick();
+        if stick != Vec2::ZERO {
+            stick
+        } else {
+            self.touch.stick()
+        }
+    }
+}
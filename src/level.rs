@@ -0,0 +1,133 @@
+//! The levels that can be played and their stored high scores.
+use crate::{ghost::Ghost, replay::Replay};
+
+/// One step of a level's wave script.
+pub struct Wave {
+    /// Seconds after the previous wave, or after the start for the first one.
+    pub after: f32,
+    pub count: usize,
+    /// Species names and how likely each is. Empty uses every species at its own
+    /// `spawn_weight`.
+    pub species: &'static [(&'static str, f32)],
+    /// Names of the spawn regions to use. Empty uses all of them.
+    pub regions: &'static [&'static str],
+}
+impl Wave {
+    /// A wave of every species from every region.
+    pub const fn of(after: f32, count: usize) -> Self {
+        Self {
+            after,
+            count,
+            species: &[],
+            regions: &[],
+        }
+    }
+}
+pub struct Level {
+    /// Used in storage keys, so it must never change once a level has shipped.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub tilemap: &'static str,
+    pub time_limit: f32,
+    /// Played in order. Once it runs out the last wave repeats, `after` seconds apart.
+    pub waves: &'static [Wave],
+    /// No wave spawns more mice than would bring the live ones over this.
+    pub max_mice: usize,
+    /// How much bigger and more frequent waves get by the end of the round. At 0.5,
+    /// the last waves are half as big again and come half as often again.
+    pub ramp: f32,
+    /// How close to the cat mice can spawn.
+    pub spawn_distance: f32,
+    /// Only spawn mice where the player can't see them appear.
+    pub spawn_off_screen: bool,
+}
+pub static LEVELS: [Level; 2] = [
+    Level {
+        id: "kitchen",
+        name: "Kitchen",
+        tilemap: include_str!("../assets/tilemap.tmx"),
+        time_limit: 30.0,
+        waves: &[
+            Wave {
+                regions: &["kitchen"],
+                ..Wave::of(0.0, 20)
+            },
+            Wave {
+                regions: &["living_room"],
+                ..Wave::of(8.0, 25)
+            },
+            Wave {
+                species: &[("mouse", 4.0), ("sneaky", 2.0), ("golden", 1.0)],
+                ..Wave::of(8.0, 30)
+            },
+        ],
+        max_mice: 60,
+        ramp: 0.5,
+        spawn_distance: 100.0,
+        spawn_off_screen: false,
+    },
+    Level {
+        id: "pantry",
+        name: "Pantry",
+        tilemap: include_str!("../assets/pantry.tmx"),
+        time_limit: 45.0,
+        waves: &[
+            Wave::of(0.0, 10),
+            Wave {
+                regions: &["by_hole"],
+                species: &[("mouse", 3.0), ("fat", 1.0)],
+                ..Wave::of(6.0, 8)
+            },
+            Wave::of(6.0, 12),
+        ],
+        max_mice: 30,
+        ramp: 0.5,
+        spawn_distance: 100.0,
+        spawn_off_screen: true,
+    },
+];
+impl Level {
+    fn high_score_key(&self) -> String {
+        format!("high_score_{}", self.id)
+    }
+    pub fn load_high_score(&self) -> u32 {
+        let storage = quad_storage::LocalStorage::default();
+        storage
+            .get(&self.high_score_key())
+            // Scores from before levels existed were all set on the first level.
+            .or_else(|| (self.id == LEVELS[0].id).then(|| storage.get("high_score"))?)
+            .unwrap_or("0".to_string())
+            .parse::<u32>()
+            .unwrap_or_default()
+    }
+    pub fn save_high_score(&self, score: u32) {
+        let mut storage = quad_storage::LocalStorage::default();
+        storage.set(&self.high_score_key(), &score.to_string());
+    }
+    fn replay_key(&self) -> String {
+        format!("replay_{}", self.id)
+    }
+    /// The run that set the high score, if it was recorded and still plays back under the
+    /// current rules.
+    pub fn load_replay(&self) -> Option<Replay> {
+        let storage = quad_storage::LocalStorage::default();
+        let replay: Replay = storage.get(&self.replay_key())?.parse().ok()?;
+        replay.check(self).is_ok().then_some(replay)
+    }
+    pub fn save_replay(&self, replay: &Replay) {
+        let mut storage = quad_storage::LocalStorage::default();
+        storage.set(&self.replay_key(), &replay.to_string());
+    }
+    fn ghost_key(&self) -> String {
+        format!("ghost_{}", self.id)
+    }
+    /// The path of the run that set the high score.
+    pub fn load_ghost(&self) -> Option<Ghost> {
+        let storage = quad_storage::LocalStorage::default();
+        storage.get(&self.ghost_key())?.parse().ok()
+    }
+    pub fn save_ghost(&self, ghost: &Ghost) {
+        let mut storage = quad_storage::LocalStorage::default();
+        storage.set(&self.ghost_key(), &ghost.to_string());
+    }
+}
@@ -1,10 +1,31 @@
 use asefile::AsepriteFile;
+use ghost::Ghost;
 use image::*;
+use input::{Action, Bindings, Controls};
+use level::{LEVELS, Level};
 use macroquad::{
     miniquad::{BlendFactor, BlendState, BlendValue, Equation},
     prelude::*,
 };
-use std::{collections::HashMap, f32::consts::PI, sync::LazyLock, vec};
+use map::{MAP_SCALE_FACTOR, Map, TILE_SIZE, TileTexture};
+use replay::{Replay, ReplayError};
+use sim::{Input, TICK, VIEW_SIZE, World};
+use std::{f32::consts::PI, sync::LazyLock};
+use tiled::Flip;
+use ui::Ui;
+mod collision;
+mod file;
+mod flow_field;
+mod focus;
+mod ghost;
+mod input;
+mod level;
+mod map;
+mod replay;
+mod sim;
+mod species;
+mod tiled;
+mod ui;
 fn load_ase_texture(bytes: &[u8], layer: Option<u32>, frame: Option<u32>) -> Texture2D {
     let img = AsepriteFile::read(bytes).unwrap();
     let frame = frame.unwrap_or(0);
@@ -36,7 +57,6 @@ fn create_camera(dimensions: Vec2) -> Camera2D {
 }
 pub fn load_animation_from_tag(bytes: &[u8], tag: &str) -> (Vec<(Texture2D, u32)>, u32) {
     let file = AsepriteFile::read(bytes).unwrap();
-    dbg!(tag);
     let tag = file.tag_by_name(tag).unwrap();
     let start = tag.from_frame();
     let end = tag.to_frame();
@@ -63,7 +83,14 @@ struct Spritesheet {
     height: f32,
 }
 impl Spritesheet {
-    fn draw_from(&self, world_pos: Vec2, texture_coord: (u8, u8), scale: f32) {
+    fn draw_from(&self, world_pos: Vec2, texture_coord: (u32, u32), flip: Flip, scale: f32) {
+        // Tiled applies the diagonal flip first, which is a quarter turn followed by a
+        // horizontal mirror. Macroquad mirrors before rotating, so the flags swap axes.
+        let (rotation, flip_x, flip_y) = if flip.diagonal {
+            (PI / 2.0, flip.vertical, !flip.horizontal)
+        } else {
+            (0.0, flip.horizontal, flip.vertical)
+        };
         draw_texture_ex(
             &self.texture,
             world_pos.x,
@@ -77,6 +104,9 @@ impl Spritesheet {
                     h: self.height,
                 }),
                 dest_size: Some(vec2(self.widht, self.height) * scale),
+                rotation,
+                flip_x,
+                flip_y,
                 ..Default::default()
             },
         )
@@ -86,412 +116,181 @@ type Animation = (Vec<(Texture2D, u32)>, u32);
 struct PlayerAnimations {
     walk: Animation,
 }
-const CAT_SPEED: f32 = 200.0;
-struct Cat {
-    pos: Vec2,
-    size: Vec2,
-    direction: Vec2,
-    animations: PlayerAnimations,
-    last_rotation: f32,
-}
-impl Cat {
-    fn new() -> Self {
-        let animations = PlayerAnimations {
-            walk: load_animation_from_tag(include_bytes!("../assets/cat.ase"), "walk"),
-        };
-
-        Self {
-            last_rotation: 0.0,
-            pos: vec2(750.0, 250.0),
-            size: vec2(
-                animations.walk.0[0].0.width(),
-                animations.walk.0[0].0.height(),
-            ),
-            direction: Vec2::ZERO,
-            animations,
-        }
-    }
-    fn update(&mut self, map: &Map) {
-        let mut animation = &self.animations.walk;
-        let mut direction = Vec2::ZERO;
-        if is_key_down(KeyCode::A) {
-            direction.x += -1.0;
-            animation = &self.animations.walk;
-        }
-        if is_key_down(KeyCode::D) {
-            direction.x += 1.0;
-            animation = &self.animations.walk;
-        }
-        if is_key_down(KeyCode::S) {
-            direction.y += 1.0;
-            animation = &self.animations.walk;
-        }
-        if is_key_down(KeyCode::W) {
-            direction.y += -1.0;
-            animation = &self.animations.walk;
-        }
-        let rotation = if direction == Vec2::ZERO {
-            self.last_rotation
-        } else {
-            0.5 * PI + direction.y.atan2(direction.x)
-        };
-        self.last_rotation = rotation;
-        self.direction += direction.normalize_or_zero();
-        let shrunk_collision = 4.0;
-        let collision_points = [
-            (shrunk_collision, shrunk_collision),
-            (self.size.x - shrunk_collision, shrunk_collision),
-            (shrunk_collision, self.size.y - shrunk_collision),
-            (
-                self.size.x - shrunk_collision,
-                self.size.y - shrunk_collision,
-            ),
-        ];
-
-        for p in collision_points.iter() {
-            let map_pos = (self.pos + self.direction + vec2(p.0, p.1)) / (16.0 * MAP_SCALE_FACTOR);
-            let pottential_collider =
-                &map.tiles[map_pos.y as usize * map.width as usize + map_pos.x as usize];
-
-            if pottential_collider.collision {
-                let x0 = map_pos.x.floor() * 16.0 * MAP_SCALE_FACTOR - p.0;
-                let x1 = (map_pos.x.floor() + 1.0) * MAP_SCALE_FACTOR * 16.0 - p.0;
-                let y0 = map_pos.y.floor() * 16.0 * MAP_SCALE_FACTOR - p.1;
-                let y1 = map_pos.y.ceil() * MAP_SCALE_FACTOR * 16.0 - p.1;
-                if self.pos.y + self.direction.y != y0 {
-                    self.pos.x = self.pos.x.clamp(x0, x1);
-                    self.pos.y = self.pos.y.clamp(y0, y1);
-                    if self.pos.x == x0 || self.pos.x == x1 {
-                        self.direction.x = 0.0;
-                    } else if self.pos.y == y0 || self.pos.y == y1 {
-                        self.direction.y = 0.0;
-                    }
-                }
-
-                // break;
-            }
-        }
-        self.pos += self.direction.normalize_or_zero() * CAT_SPEED * get_frame_time();
-
-        self.direction *= 0.8;
-        if self.direction.x.abs() < 0.3 && self.direction.y.abs() < 0.3 {
-            self.direction = Vec2::ZERO;
-        }
-        if is_key_down(KeyCode::F) {
-            dbg!(self.pos, self.direction);
-        }
-        let mut time = (get_time() * 1000.0) % animation.1 as f64;
-        for i in &animation.0 {
-            if time <= i.1 as f64 {
-                draw_texture_ex(
-                    &i.0,
-                    self.pos.x,
-                    self.pos.y,
-                    WHITE,
-                    DrawTextureParams {
-                        rotation,
-                        ..Default::default()
-                    },
-                );
-                break;
-            } else {
-                time -= i.1 as f64;
-            }
-        }
+/// Frames longer than this are simulated as if they were this long, so a stalled tab
+/// doesn't have to catch up on minutes of ticks.
+const MAX_FRAME_TIME: f32 = 0.25;
+/// The movement held down right now.
+fn read_input(controls: &Controls) -> Input {
+    Input {
+        direction: controls.movement(),
     }
 }
-struct Mouse<'a> {
-    scare_timer: f32,
-    random_direction_cooldown: f32,
-    is_rainbow: bool,
-    pos: Vec2,
-    speed: f32,
-    size: Vec2,
-    direction: Vec2,
-    animation: &'a Animation,
+/// Where the game view goes on screen, as large as fits. In landscape it is centred, and
+/// in portrait it sits at the top, which leaves room for touch controls below.
+fn viewport() -> Rect {
+    let size = VIEW_SIZE * (screen_width() / VIEW_SIZE.x).min(screen_height() / VIEW_SIZE.y);
+    let x = if screen_width() > screen_height() {
+        (screen_width() - size.x) / 2.0
+    } else {
+        0.0
+    };
+    Rect::new(x, 0.0, size.x, size.y)
 }
 
-const SCREEN_SIZE: Vec2 = Vec2 { x: 160.0, y: 160.0 };
-
-#[derive(Debug, PartialEq)]
-
-enum Layer {
-    Floor,
-    Decor,
-    Decor2,
-    Collision,
-}
-impl Layer {
-    fn from_str(string: &str) -> Self {
-        match string {
-            "floor" => Self::Floor,
-            "decor" => Self::Decor,
-            "collision" => Self::Collision,
-            "decor2" => Self::Decor2,
-            _ => unreachable!(),
-        }
-    }
+const CHUNK_SIZE: u32 = 16;
+struct MapChunk {
+    /// Top left corner in tiles.
+    x: u32,
+    y: u32,
+    /// The static tiles of each layer that has any in this chunk, drawn at one pixel per
+    /// texel.
+    layers: Vec<(usize, RenderTarget)>,
+    /// Tiles in this chunk with at least one animated texture.
+    animated: Vec<usize>,
 }
-#[derive(Debug)]
-struct Tile {
-    textures: Vec<(u8, u8)>,
-    layers: Vec<Layer>,
-    collision: bool,
+/// Draws a `Map` from render targets baked once per chunk and layer, so a frame only
+/// touches the chunks the camera can see.
+struct MapRenderer {
+    chunks: Vec<MapChunk>,
 }
-fn load_tilemap(tilemap: &str, tileset: &str) -> (Vec<Tile>, u32) {
-    let tile_set_width = tileset
-        .split_once("columns=\"")
-        .unwrap()
-        .1
-        .split_once("\"")
-        .unwrap()
-        .0
-        .parse::<u8>()
-        .unwrap();
-    dbg!(tile_set_width);
-    fn get_area(chunks: &HashMap<(i32, i32), [u8; 256]>) -> (i32, i32, i32, i32) {
-        let posses: Vec<(i32, i32, i32, i32)> = chunks
-            .iter()
-            .map(|f| {
-                let lowest_x = f.0.0
-                    + f.1
-                        .iter()
-                        .enumerate()
-                        .filter(|f| *f.1 != 0)
-                        .map(|f| f.0 % 16)
-                        .min()
-                        .unwrap() as i32;
-                let highest_x = f.0.0
-                    + f.1
-                        .iter()
-                        .enumerate()
-                        .filter(|f| *f.1 != 0)
-                        .map(|f| f.0 % 16)
-                        .max()
-                        .unwrap() as i32;
-                let lowest_y = f.0.1
-                    + f.1
+impl MapRenderer {
+    /// Bakes every chunk, which switches the camera. Callers have to set their own
+    /// camera again before drawing.
+    fn new(map: &Map) -> Self {
+        let tile_size = 16.0;
+        let chunk_pixels = CHUNK_SIZE as f32 * tile_size;
+        let mut chunks = Vec::new();
+        for y in (0..map.height()).step_by(CHUNK_SIZE as usize) {
+            for x in (0..map.width).step_by(CHUNK_SIZE as usize) {
+                let tiles: Vec<usize> = (y..(y + CHUNK_SIZE).min(map.height()))
+                    .flat_map(|ty| {
+                        (x..(x + CHUNK_SIZE).min(map.width))
+                            .map(move |tx| (ty * map.width + tx) as usize)
+                    })
+                    .collect();
+                let mut chunk = MapChunk {
+                    x,
+                    y,
+                    layers: Vec::new(),
+                    animated: tiles
                         .iter()
-                        .enumerate()
-                        .filter(|f| *f.1 != 0)
-                        .map(|f| f.0 / 16)
-                        .min()
-                        .unwrap() as i32;
-                let highest_y = f.0.1
-                    + f.1
-                        .iter()
-                        .enumerate()
-                        .filter(|f| *f.1 != 0)
-                        .map(|f| f.0 / 16)
-                        .max()
-                        .unwrap() as i32;
-                (lowest_x, lowest_y, highest_x, highest_y)
-            })
-            .collect();
-        dbg!(&posses);
-        let lowest_x = posses.iter().map(|f| f.0).min().unwrap_or(posses[0].0);
-        let highest_x = posses.iter().map(|f| f.2).max().unwrap();
-        let lowest_y = posses.iter().map(|f| f.1).min().unwrap_or(posses[0].1);
-        let highest_y = posses.iter().map(|f| f.3).max().unwrap_or(posses[0].3);
-
-        (lowest_x, lowest_y, highest_x, highest_y)
-    }
-    let mut layers: Vec<(HashMap<(i32, i32), [u8; 256]>, &str)> = Vec::new();
-    for layer in tilemap.split("<layer").skip(1) {
-        let name = layer
-            .split_once("name=\"")
-            .unwrap()
-            .1
-            .split_once("\"")
-            .unwrap()
-            .0;
-        dbg!(name);
-        let mut chunks: HashMap<(i32, i32), [u8; 256]> = HashMap::new();
-        for chunk in layer.split("<chunk").skip(1) {
-            let x = chunk
-                .split_once("x=\"")
-                .unwrap()
-                .1
-                .split_once("\"")
-                .unwrap()
-                .0
-                .parse::<i32>()
-                .unwrap();
-            let y = chunk
-                .split_once("y=\"")
-                .unwrap()
-                .1
-                .split_once("\"")
-                .unwrap()
-                .0
-                .parse::<i32>()
-                .unwrap();
-
-            let chunk = chunk
-                .split_once("\r\n")
-                .unwrap()
-                .1
-                .split_once("\r\n</")
-                .unwrap()
-                .0;
-            let mut data = [0; 256];
-
-            for (index, id) in chunk.split(",").enumerate() {
-                let id = if id.contains("\r\n") {
-                    &id.replace("\r\n", "")
-                } else {
-                    id
+                        .copied()
+                        .filter(|f| map.tiles[*f].textures.iter().any(|t| t.animation.is_some()))
+                        .collect(),
                 };
-
-                data[index] = id.parse::<u8>().unwrap();
-            }
-            if data.iter().all(|f| *f == 0) {
-                println!("chunk x: {},y: {} is empty ", x, y);
-                continue;
-            } else {
-                println!("chunk is full of juice x: {}y:{}", x, y)
+                for layer in (0..map.layers.len()).filter(|f| map.layers[*f].visible) {
+                    let textures: Vec<(usize, &TileTexture)> = tiles
+                        .iter()
+                        .flat_map(|f| map.tiles[*f].textures.iter().map(move |t| (*f, t)))
+                        .filter(|f| f.1.layer == layer && f.1.animation.is_none())
+                        .collect();
+                    if textures.is_empty() {
+                        continue;
+                    }
+                    let rt = render_target(chunk_pixels as u32, chunk_pixels as u32);
+                    rt.texture.set_filter(FilterMode::Nearest);
+                    set_camera(&Camera2D {
+                        zoom: vec2(2.0 / chunk_pixels, 2.0 / chunk_pixels),
+                        target: vec2(chunk_pixels, chunk_pixels) / 2.0,
+                        render_target: Some(rt.clone()),
+                        ..Default::default()
+                    });
+                    clear_background(BLANK);
+                    for (index, texture) in textures {
+                        let local = map.tile_pos(index) / MAP_SCALE_FACTOR
+                            - vec2(x as f32, y as f32) * tile_size;
+                        SPRITESHEET.draw_from(local, texture.coord, texture.flip, 1.0);
+                    }
+                    chunk.layers.push((layer, rt));
+                }
+                chunks.push(chunk);
             }
-
-            chunks.insert((x, y), data);
         }
-        layers.push((chunks, name));
+        set_default_camera();
+        Self { chunks }
     }
-    let layers_pos: Vec<(i32, i32, i32, i32)> = layers.iter().map(|f| get_area(&f.0)).collect();
-    dbg!(&layers_pos);
-    let area: (i32, i32, i32, i32) = (
-        layers_pos.iter().map(|f| f.0).min().unwrap(),
-        layers_pos.iter().map(|f| f.1).min().unwrap(),
-        layers_pos.iter().map(|f| f.2).max().unwrap(),
-        layers_pos.iter().map(|f| f.3).max().unwrap(),
-    );
-    dbg!(area);
-    let mut tiles: Vec<Tile> = Vec::with_capacity(((area.2 - area.0) * (area.3 - area.1)) as usize);
-
-    for y in area.1..area.3 + 1 {
-        for x in area.0..area.2 + 1 {
-            let mut tile = Tile {
-                textures: vec![],
-                collision: false,
-                layers: Vec::new(),
-            };
-            for (chunks, name) in layers.iter() {
-                if let Some(chunk) = chunks.get(&(
-                    ((x as f32 / 16.0).floor() * 16.0) as i32,
-                    ((y as f32 / 16.0).floor() * 16.0) as i32,
-                )) {
-                    let id = chunk[(y % 16 * 16 + x % 16) as usize];
-
-                    if id != 0 {
-                        let id = id - 1;
-                        if name.contains("collision") {
-                            tile.collision = true;
+    /// Draws the layers on one side of the actors, skipping chunks and tiles outside `view`.
+    fn draw(&self, map: &Map, view: Rect, above_actors: bool) {
+        let chunk_size = CHUNK_SIZE as f32 * TILE_SIZE;
+        let visible: Vec<&MapChunk> = self
+            .chunks
+            .iter()
+            .filter(|f| {
+                let pos = map.tile_pos((f.y * map.width + f.x) as usize);
+                view.overlaps(&Rect::new(pos.x, pos.y, chunk_size, chunk_size))
+            })
+            .collect();
+        let layers = (0..map.layers.len())
+            .filter(|f| map.layers[*f].visible && map.layers[*f].above_actors == above_actors);
+        for layer in layers {
+            for chunk in visible.iter() {
+                let pos = map.tile_pos((chunk.y * map.width + chunk.x) as usize);
+                if let Some((_, rt)) = chunk.layers.iter().find(|f| f.0 == layer) {
+                    draw_texture_ex(
+                        &rt.texture,
+                        pos.x,
+                        pos.y,
+                        WHITE,
+                        DrawTextureParams {
+                            dest_size: Some(vec2(chunk_size, chunk_size)),
+                            ..Default::default()
+                        },
+                    );
+                }
+                for index in chunk.animated.iter().copied() {
+                    let pos = map.tile_pos(index);
+                    if !view.overlaps(&Rect::new(pos.x, pos.y, TILE_SIZE, TILE_SIZE)) {
+                        continue;
+                    }
+                    for texture in map.tiles[index].textures.iter() {
+                        if texture.layer == layer && texture.animation.is_some() {
+                            SPRITESHEET.draw_from(
+                                pos,
+                                map.current_coord(texture, get_time()),
+                                texture.flip,
+                                MAP_SCALE_FACTOR,
+                            );
                         }
-                        tile.textures
-                            .push((id % tile_set_width, id / tile_set_width));
-                        tile.layers.push(Layer::from_str(name));
                     }
                 }
             }
-            tiles.push(tile);
         }
     }
-    (tiles, (area.2 + 1 - area.0) as u32)
 }
-struct Map {
-    tiles: Vec<Tile>,
-    width: u32,
-    real_width: f32,
-    real_height: f32,
-}
-impl Map {
-    fn new() -> Self {
-        let map = load_tilemap(
-            include_str!("../assets/tilemap.tmx"),
-            include_str!("../assets/spritesheet.tsx"),
-        );
-        Self {
-            real_width: map.1 as f32 * 16.0 * MAP_SCALE_FACTOR,
-            real_height: (map.0.len() as u32 / map.1) as f32 * 16.0 * MAP_SCALE_FACTOR,
-            tiles: map.0,
-            width: map.1,
-        }
-    }
-    fn draw_map(&self) {
-        for (index, tile) in self.tiles.iter().enumerate() {
-            for text in &tile.textures {
-                SPRITESHEET.draw_from(
-                    vec2(
-                        (index as u32 % self.width) as f32 * 16.0 * MAP_SCALE_FACTOR,
-                        (index as u32 / self.width) as f32 * 16.0 * MAP_SCALE_FACTOR,
-                    ),
-                    *text,
-                    MAP_SCALE_FACTOR,
-                );
-            }
-        }
-    }
+/// The world space rectangle a camera shows.
+fn camera_view(camera: &Camera2D) -> Rect {
+    let size = vec2(2.0 / camera.zoom.x, 2.0 / camera.zoom.y).abs();
+    Rect::new(
+        camera.target.x - size.x / 2.0,
+        camera.target.y - size.y / 2.0,
+        size.x,
+        size.y,
+    )
 }
 static SPRITESHEET: LazyLock<Spritesheet> = std::sync::LazyLock::new(|| Spritesheet {
     texture: load_ase_texture(include_bytes!("../assets/spritesheet.ase"), None, None),
     widht: 16.0,
     height: 16.0,
 });
-static MOUSE_ANIMATION: LazyLock<Animation> = std::sync::LazyLock::new(|| {
-    load_animation_from_tag(include_bytes!("../assets/mouse.ase"), "walk")
+/// The walk animation of every sprite in `species::SPRITES`, in the same order.
+static MOUSE_ANIMATIONS: LazyLock<Vec<Animation>> = std::sync::LazyLock::new(|| {
+    species::SPRITES
+        .iter()
+        .map(|f| load_animation_from_tag(f.1, "walk"))
+        .collect()
 });
-const MAP_SCALE_FACTOR: f32 = 3.0;
-struct Spawner {
-    clock: f32,
-}
-impl Spawner {
-    fn new() -> Self {
-        Self { clock: 0.0 }
-    }
-    fn spawn_wave(entities: &mut Vec<Mouse>, map: &Map) {
-        let wave_size = 30;
-        let mut dealt_with = Vec::with_capacity(30);
-        while dealt_with.len() < wave_size {
-            let rand = rand::gen_range(0, map.tiles.len() - 1);
-            if dealt_with.contains(&rand) {
-                continue;
-            }
-            let tile = &map.tiles[rand];
-            if tile.layers.len() == 1 && tile.layers[0] == Layer::Floor {
-                dealt_with.push(rand);
-                let size = vec2(
-                    MOUSE_ANIMATION.0[0].0.width(),
-                    MOUSE_ANIMATION.0[0].0.height(),
-                );
-                let rainbow = rand::gen_range(0, 30) == 0;
-                entities.push(Mouse {
-                    speed: if rainbow { 250.0 } else { 150.0 },
-                    scare_timer: 0.0,
-                    random_direction_cooldown: 0.0,
-
-                    is_rainbow: rainbow,
-                    size,
-                    pos: vec2(
-                        (rand as u32 % map.width) as f32 * 16.0 * MAP_SCALE_FACTOR,
-                        (rand as u32 / map.width) as f32 * 16.0 * MAP_SCALE_FACTOR,
-                    ),
-                    direction: Vec2::ZERO,
-                    animation: &MOUSE_ANIMATION,
-                });
-            }
-        }
-    }
-    fn update(&mut self, entities: &mut Vec<Mouse>, map: &Map) {
-        self.clock -= get_frame_time();
-        if self.clock <= 0.0 {
-            self.clock = 10.0;
-            Self::spawn_wave(entities, map)
-        }
-    }
-}
 #[derive(PartialEq)]
 enum State {
     Menu,
     Game,
+    /// A round is in progress but frozen behind the pause menu.
+    Paused,
+}
+/// What was picked on the pause menu.
+enum PauseChoice {
+    Resume,
+    Restart,
+    Quit,
 }
 const RAIINBOW_FRAGMENT_SHADER: &str = "#version 100
 precision lowp float;
@@ -559,38 +358,159 @@ static RAINBOW_SHADER: LazyLock<Material> = std::sync::LazyLock::new(|| {
 static FONT: LazyLock<Font> =
     LazyLock::new(|| load_ttf_font_from_bytes(include_bytes!("../assets/GOUDYSTO.TTF")).unwrap());
 
-struct Debug {
-    mouse_cam: bool,
-    mouse: usize,
+/// Draws a `World` into the camera's render target.
+struct WorldRenderer {
+    camera: Camera2D,
+    map_renderer: MapRenderer,
+    cat: PlayerAnimations,
+    /// Seconds into the cat's walk animation, which plays as fast as the cat moves.
+    walk_clock: f64,
 }
-static mut DEBUG: Debug = Debug {
-    mouse_cam: false,
-    mouse: 0,
-};
+impl WorldRenderer {
+    fn new(map: &Map) -> Self {
+        Self {
+            cat: PlayerAnimations {
+                walk: load_animation_from_tag(include_bytes!("../assets/cat.ase"), "walk"),
+            },
+            map_renderer: MapRenderer::new(map),
+            walk_clock: 0.0,
+            camera: create_camera(VIEW_SIZE),
+        }
+    }
+    /// Draws the world `alpha` of the way from the previous tick to the last one, with a
+    /// ghost cat at a position and heading. The cat's walk plays on by `dt` seconds.
+    fn render(&mut self, world: &World, ghost: Option<(Vec2, Vec2)>, alpha: f32, dt: f32) {
+        self.camera.target = world.cat.render_pos(alpha);
+        set_camera(&self.camera);
 
-struct Game<'a> {
-    minimap_cam: Camera2D,
-    cat: Cat,
-    mice: Vec<Mouse<'a>>,
-    camera: Camera2D,
-    map: Map,
-    spawner: Spawner,
-    timer: f32,
+        let view = camera_view(&self.camera);
+        self.map_renderer.draw(&world.map, view, false);
+        RAINBOW_SHADER.set_uniform("time", get_time() as f32 * 8.0);
+        for mouse in world.mice.iter() {
+            let species = mouse.species;
+            if species.material == species::Material::Rainbow {
+                gl_use_material(&RAINBOW_SHADER);
+            }
+            let sprite = species::SPRITES
+                .iter()
+                .position(|f| f.0 == species.sprite)
+                .unwrap();
+            let [r, g, b] = species.tint;
+            draw_animation(
+                &MOUSE_ANIMATIONS[sprite],
+                get_time(),
+                mouse.render_pos(alpha),
+                mouse.direction.y.atan2(mouse.direction.x) + PI / 2.0,
+                Color::from_rgba(r, g, b, 255),
+                Some(mouse.size),
+            );
+            if species.material == species::Material::Rainbow {
+                gl_use_default_material();
+            }
+        }
+        if let Some((pos, heading)) = ghost {
+            let rotation = if heading == Vec2::ZERO {
+                0.0
+            } else {
+                0.5 * PI + heading.y.atan2(heading.x)
+            };
+            draw_animation(
+                &self.cat.walk,
+                get_time(),
+                pos,
+                rotation,
+                WHITE.with_alpha(0.35),
+                None,
+            );
+        }
+        self.walk_clock += (dt * world.cat.speed) as f64;
+        draw_animation(
+            &self.cat.walk,
+            self.walk_clock,
+            world.cat.render_pos(alpha),
+            world.cat.rotation,
+            WHITE,
+            None,
+        );
+        self.map_renderer.draw(&world.map, view, true);
+    }
+}
+/// Draws the frame a looping animation shows `time` seconds in, stretched to `size` if
+/// given.
+fn draw_animation(
+    animation: &Animation,
+    time: f64,
+    pos: Vec2,
+    rotation: f32,
+    color: Color,
+    size: Option<Vec2>,
+) {
+    let mut time = (time * 1000.0) % animation.1 as f64;
+    for i in &animation.0 {
+        if time <= i.1 as f64 {
+            draw_texture_ex(
+                &i.0,
+                pos.x,
+                pos.y,
+                color,
+                DrawTextureParams {
+                    rotation,
+                    dest_size: size,
+                    ..Default::default()
+                },
+            );
+            break;
+        } else {
+            time -= i.1 as f64;
+        }
+    }
+}
+struct Game {
+    level: &'static Level,
+    world: World,
+    renderer: WorldRenderer,
+    /// The input of every tick so far, or of the whole run when watching a replay.
+    replay: Replay,
+    /// The inputs still to play when watching a replay.
+    playback: Option<std::vec::IntoIter<Input>>,
+    /// The path of this run so far.
+    ghost: Ghost,
+    /// The path of the high score run, raced against.
+    best: Option<Ghost>,
+    /// Frame time not yet consumed by a tick.
+    accumulator: f32,
     fade_out_clock: f32,
     done: bool,
     go_back_button: Button,
     go_to_menu: bool,
-    kills: u32,
+    /// Buttons on the results screen.
+    ui: Ui,
     clock: Texture2D,
     mouse_icon: Texture2D,
-    scale_factor: f32,
+    /// Where the game view is drawn on screen.
+    viewport: Rect,
 }
-impl<'a> Game<'a> {
-    fn new() -> Self {
+impl Game {
+    fn new(level: &'static Level, seed: u64) -> Self {
+        Self::with_replay(level, Replay::new(level.id, seed), false)
+    }
+    /// Plays back a recorded run of `level`.
+    fn watch(level: &'static Level, replay: Replay) -> Self {
+        Self::with_replay(level, replay, true)
+    }
+    fn with_replay(level: &'static Level, replay: Replay, playback: bool) -> Self {
         let button = load_ase_texture(include_bytes!("../assets/back.ase"), None, None);
+        let world = World::new(level, replay.seed)
+            .unwrap_or_else(|err| panic!("could not load tilemap for {}: {err}", level.name));
+        let mut ghost = Ghost::default();
+        ghost.record(&world);
         Self {
-            minimap_cam: create_camera(vec2(100.0, 100.0)),
-            scale_factor: 0.0,
+            level,
+            ghost,
+            best: level.load_ghost().filter(|_| !playback),
+            playback: playback.then(|| replay.inputs().collect::<Vec<_>>().into_iter()),
+            replay,
+            viewport: viewport(),
             mouse_icon: load_ase_texture(include_bytes!("../assets/mouse_icon.ase"), None, None),
             clock: load_ase_texture(include_bytes!("../assets/clock.aseprite"), None, None),
             go_back_button: Button {
@@ -603,153 +523,41 @@ impl<'a> Game<'a> {
                 texture: button,
             },
             go_to_menu: false,
-            kills: 0,
+            ui: Ui::default(),
             done: false,
             fade_out_clock: 0.0,
-            timer: 30.0,
-            spawner: Spawner::new(),
-            map: Map::new(),
-            cat: Cat::new(),
-            mice: Vec::new(),
-            camera: create_camera(SCREEN_SIZE),
-        }
-    }
-    fn draw_mice(&self) {
-        for mouse in self.mice.iter() {
-            if mouse.is_rainbow {
-                gl_use_material(&RAINBOW_SHADER);
-            }
-            let mut time = (get_time() * 1000.0) % mouse.animation.1 as f64;
-            for i in &mouse.animation.0 {
-                if time <= i.1 as f64 {
-                    draw_texture_ex(
-                        &i.0,
-                        mouse.pos.x,
-                        mouse.pos.y,
-                        WHITE,
-                        DrawTextureParams {
-                            rotation: mouse.direction.y.atan2(mouse.direction.x) + PI / 2.0,
-
-                            ..Default::default()
-                        },
-                    );
-                    break;
-                } else {
-                    time -= i.1 as f64;
-                }
-            }
-            if mouse.is_rainbow {
-                gl_use_default_material();
-            }
+            accumulator: 0.0,
+            renderer: WorldRenderer::new(&world.map),
+            world,
         }
     }
     fn draw_camera(&self) {
         set_default_camera();
         draw_texture_ex(
-            &self.camera.render_target.as_ref().unwrap().texture,
-            0.0,
-            0.0,
+            &self.renderer.camera.render_target.as_ref().unwrap().texture,
+            self.viewport.x,
+            self.viewport.y,
             WHITE,
             DrawTextureParams {
-                dest_size: Some(SCREEN_SIZE * self.scale_factor),
+                dest_size: Some(self.viewport.size()),
                 ..Default::default()
             },
         );
-        set_camera(&self.camera);
+        set_camera(&self.renderer.camera);
         clear_background(BLACK);
     }
-    fn mouse_eatery(&mut self) {
-        self.mice.retain(|f| {
-            let collisions = [
-                (0.0, 0.0),
-                (f.size.x, 0.0),
-                (0.0, f.size.y),
-                (f.size.x, f.size.y),
-            ];
-            let mut collide = false;
-            for p in collisions {
-                if p.0 + f.pos.x >= self.cat.pos.x
-                    && p.0 + f.pos.x <= self.cat.pos.x + self.cat.size.x
-                    && f.pos.y + p.1 <= self.cat.pos.y + self.cat.size.y
-                    && f.pos.y + p.1 > self.cat.pos.y
-                {
-                    collide = true;
-                    self.kills += if f.is_rainbow { 3 } else { 1 };
-                }
-            }
-
-            !collide
-        })
-    }
-    fn mouse_behaviour(&mut self) {
-        for mouse in self.mice.iter_mut() {
-            mouse.scare_timer = (mouse.scare_timer - get_frame_time()).max(0.0);
-            if (((mouse.pos.x - self.cat.pos.x).powi(2) + (mouse.pos.y - self.cat.pos.y).powi(2))
-                .sqrt())
-            .abs()
-                < 100.0
-                && mouse.scare_timer == 0.0
-            {
-                mouse.scare_timer = if mouse.is_rainbow { 0.5 } else { 0.3 };
-                mouse.direction = (mouse.pos - self.cat.pos).normalize_or_zero();
-            } else if mouse.random_direction_cooldown < 0.0 {
-                mouse.direction = vec2(rand::gen_range(-1.0, 1.0), rand::gen_range(-1.0, 1.0))
-                    .normalize_or_zero();
-                mouse.random_direction_cooldown = rand::gen_range(1.0, 5.0);
-            } else {
-                mouse.random_direction_cooldown -= get_frame_time();
-            }
-            let collisions = [
-                (0.0, 0.0),
-                (mouse.size.x, 0.0),
-                (0.0, mouse.size.y),
-                (mouse.size.x, mouse.size.y),
-            ];
-            for p in collisions.iter() {
-                let map_pos =
-                    (mouse.pos + mouse.direction * mouse.speed * get_frame_time() + vec2(p.0, p.1))
-                        / (16.0 * MAP_SCALE_FACTOR);
-                if map_pos.y as usize * self.map.width as usize + map_pos.x as usize + 1
-                    > self.map.tiles.len()
-                {
-                    println!("illegal mouse");
-                    break;
-                }
-                let pottential_collider = &self.map.tiles
-                    [map_pos.y as usize * self.map.width as usize + map_pos.x as usize];
-
-                if pottential_collider.collision {
-                    println!("collid");
-                    let x0 = map_pos.x.floor() * 16.0 * MAP_SCALE_FACTOR - p.0;
-                    let x1 = (map_pos.x.floor() + 1.0) * MAP_SCALE_FACTOR * 16.0 - p.0;
-                    let y0 = map_pos.y.floor() * 16.0 * MAP_SCALE_FACTOR - p.1;
-                    let y1 = map_pos.y.ceil() * MAP_SCALE_FACTOR * 16.0 - p.1;
-                    if mouse.pos.y + mouse.direction.y != y0 {
-                        mouse.pos.x = mouse.pos.x.clamp(x0, x1);
-                        mouse.pos.y = mouse.pos.y.clamp(y0, y1);
-                        if mouse.pos.x == x0 || mouse.pos.x == x1 {
-                            mouse.direction.x *= -1.0;
-                        } else if mouse.pos.y == y0 || mouse.pos.y == y1 {
-                            mouse.direction.y *= -1.0;
-                        }
-                    }
-                }
-            }
-
-            mouse.pos += mouse.direction.normalize_or_zero() * mouse.speed * get_frame_time();
-        }
-    }
-    fn fade_out_menu(&mut self) {
+    fn fade_out_menu(&mut self, controls: &Controls) {
         set_default_camera();
         clear_background(BLACK);
         self.go_back_button.rect.x = (screen_width() - self.go_back_button.rect.w) / 2.0;
         self.go_back_button.rect.y = (screen_height() - self.go_back_button.rect.h + 300.0) / 2.0;
-        draw_texture(
-            &self.go_back_button.texture,
-            self.go_back_button.rect.x,
-            self.go_back_button.rect.y,
-            WHITE,
-        );
+        self.ui.begin(1.0, Some(controls), true);
+        if self
+            .ui
+            .image_button(&self.go_back_button.texture, self.go_back_button.rect)
+        {
+            self.go_to_menu = true;
+        }
         let font_size = 30;
         draw_text_ex(
             "Good work soldier!",
@@ -772,7 +580,7 @@ impl<'a> Game<'a> {
             },
         );
         draw_text_ex(
-            &format!("{} mice!", self.kills),
+            &format!("{} mice!", self.world.kills),
             (screen_width() + 200.0) / 2.0,
             screen_height() / 2.0,
             TextParams {
@@ -782,16 +590,36 @@ impl<'a> Game<'a> {
                 ..Default::default()
             },
         );
-
-        if self.go_back_button.is_clicked(mouse_position()) {
-            self.go_to_menu = true;
-        }
+        let escaped = match self.world.escapes {
+            1 => "1 mouse got away.".to_string(),
+            escapes => format!("{escapes} mice got away."),
+        };
+        draw_text_ex(
+            &escaped,
+            (screen_width() - 550.0) / 2.0,
+            (screen_height() + 80.0) / 2.0,
+            TextParams {
+                font: Some(&FONT),
+                font_size: 20,
+                ..Default::default()
+            },
+        );
+        draw_text_ex(
+            &format!("Seed: {}", self.world.seed),
+            (screen_width() - 550.0) / 2.0,
+            (screen_height() + 150.0) / 2.0,
+            TextParams {
+                font: Some(&FONT),
+                font_size: 20,
+                ..Default::default()
+            },
+        );
     }
     fn draw_hud(&mut self) {
         set_default_camera();
 
         draw_text(
-            &((self.timer as i32).max(0)).to_string(),
+            &((self.world.timer as i32).max(0)).to_string(),
             screen_width() - 120.00,
             40.0,
             60.0,
@@ -807,7 +635,26 @@ impl<'a> Game<'a> {
                 ..Default::default()
             },
         );
-        draw_text(&self.kills.to_string(), 80.0, 55.0, 60.0, WHITE);
+        let kills = self.world.kills.to_string();
+        draw_text(&kills, 80.0, 55.0, 60.0, WHITE);
+        if let Some(best) = self
+            .best
+            .as_ref()
+            .and_then(|f| f.at(self.world.ticks as f32))
+        {
+            let delta = self.world.kills as i64 - best.2 as i64;
+            let color = match delta.signum() {
+                1 => GREEN,
+                -1 => RED,
+                _ => WHITE,
+            };
+            let x = 90.0 + measure_text(&kills, None, 60, 1.0).width;
+            draw_text(&format!("{delta:+}"), x, 50.0, 40.0, color);
+        }
+        if self.playback.is_some() {
+            let width = measure_text("REPLAY", None, 40, 1.0).width;
+            draw_text("REPLAY", (screen_width() - width) / 2.0, 40.0, 40.0, RED);
+        }
         draw_texture_ex(
             &self.mouse_icon,
             5.0,
@@ -819,27 +666,87 @@ impl<'a> Game<'a> {
             },
         );
 
-        set_camera(&self.camera);
+        set_camera(&self.renderer.camera);
     }
-    async fn update(&mut self) {
+    /// Draws the frozen round under Resume, Restart and Main Menu buttons.
+    fn pause_menu(&mut self, controls: &Controls) -> Option<PauseChoice> {
+        self.draw(controls, 0.0);
+        set_default_camera();
+        draw_rectangle(
+            0.0,
+            0.0,
+            screen_width(),
+            screen_height(),
+            BLACK.with_alpha(0.6),
+        );
+        let sf = self.viewport.w.min(self.viewport.h) / 200.0;
+        let center = self.viewport.center() / sf;
+        self.ui.begin(sf, Some(controls), false);
+        let font_size = 16.0;
+        let width = |text| measure_text(text, None, (font_size * sf) as u16, 1.0).width / sf;
+        draw_text(
+            "Paused",
+            (center.x - width("Paused") / 2.0) * sf,
+            (center.y - 30.0) * sf,
+            font_size * 1.5 * sf,
+            WHITE,
+        );
+        let mut choice = None;
+        for (row, (text, option)) in [
+            ("Resume", PauseChoice::Resume),
+            ("Restart", PauseChoice::Restart),
+            ("Main Menu", PauseChoice::Quit),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let pos = vec2(center.x - width(text) / 2.0, center.y + row as f32 * 20.0);
+            if self.ui.text_button(text, pos, font_size) {
+                choice = Some(option);
+            }
+        }
+        set_camera(&self.renderer.camera);
+        choice
+    }
+    /// Whether the round can still be paused.
+    fn is_running(&self) -> bool {
+        !self.world.is_over()
+    }
+    /// Draws the round with `dt` seconds passed since the last frame, none while paused.
+    fn draw(&mut self, controls: &Controls, dt: f32) {
+        self.viewport = viewport();
+        let alpha = (self.accumulator / TICK).min(1.0);
+        let ghost = self
+            .best
+            .as_ref()
+            .and_then(|f| f.at(self.world.ticks as f32 - 1.0 + alpha))
+            .map(|f| (f.0, f.1));
+        self.renderer.render(&self.world, ghost, alpha, dt);
+        self.draw_camera();
+        self.draw_hud();
+        controls.touch.draw();
+    }
+    async fn update(&mut self, controls: &Controls) {
         if self.done {
-            self.fade_out_menu();
+            self.fade_out_menu(controls);
         } else {
-            self.scale_factor =
-                (screen_width() / SCREEN_SIZE.x).min(screen_height() / SCREEN_SIZE.y);
-
-            self.map.draw_map();
-            RAINBOW_SHADER.set_uniform("time", get_time() as f32 * 8.0);
-            self.draw_mice();
-            self.mouse_eatery();
-
-            self.mouse_behaviour();
-            self.cat.update(&self.map);
-            self.spawner.update(&mut self.mice, &self.map);
-            self.camera.target = self.cat.pos;
-            self.draw_camera();
-            self.draw_hud();
-            if self.timer <= 0.0 {
+            self.accumulator += get_frame_time().min(MAX_FRAME_TIME);
+            let input = read_input(controls);
+            // The world stands still while the round fades out, so a replay only has to
+            // cover the ticks before the timer ran out.
+            while self.accumulator >= TICK && !self.world.is_over() {
+                let input = match self.playback.as_mut() {
+                    Some(inputs) => inputs
+                        .next()
+                        .expect("replays are checked against their round before playback"),
+                    None => self.replay.record(input),
+                };
+                self.world.update(TICK, input);
+                self.ghost.record(&self.world);
+                self.accumulator -= TICK;
+            }
+            self.draw(controls, get_frame_time());
+            if self.world.is_over() {
                 self.fade_out_clock += get_frame_time();
                 let fade_out = 2.0;
                 if self.fade_out_clock < fade_out {
@@ -851,12 +758,10 @@ impl<'a> Game<'a> {
                         screen_height(),
                         BLACK.with_alpha(self.fade_out_clock / fade_out),
                     );
-                    set_camera(&self.camera);
+                    set_camera(&self.renderer.camera);
                 } else {
                     self.done = true;
                 }
-            } else {
-                self.timer -= get_frame_time()
             }
         }
     }
@@ -865,15 +770,6 @@ struct Button {
     rect: Rect,
     texture: Texture2D,
 }
-impl Button {
-    fn is_clicked(&self, mouse_pos: (f32, f32)) -> bool {
-        mouse_pos.0 >= self.rect.x
-            && mouse_pos.0 <= self.rect.x + self.rect.w
-            && mouse_pos.1 <= self.rect.h + self.rect.y
-            && mouse_pos.1 >= self.rect.y
-            && is_mouse_button_down(MouseButton::Left)
-    }
-}
 struct Menu {
     size: (f32, f32),
     button: Button,
@@ -883,23 +779,50 @@ struct Menu {
     animation_clock: f32,
     current_animation: Option<usize>,
     play: bool,
-    high_score: u32,
+    /// Set when "Watch replay" was clicked.
+    watch: bool,
+    selected_level: usize,
+    high_scores: Vec<u32>,
+    /// The recorded high score run of each level.
+    replays: Vec<Option<Replay>>,
+    /// A replay file that was picked, checked, and is waiting to be watched.
+    imported: Option<Replay>,
+    /// Set while waiting for a picked replay file to arrive.
+    importing: bool,
+    /// How the last export or import went.
+    status: Option<String>,
+    /// Digits typed into the seed field. Empty plays a random seed.
+    seed_input: String,
+    editing_seed: bool,
+    /// Set when the last digit typed was refused for making the seed too big.
+    seed_too_big: bool,
+    show_controls: bool,
+    /// The action and slot waiting for a key on the controls screen.
+    rebinding: Option<(Action, usize)>,
+    ui: Ui,
 }
 
 impl Menu {
     fn new() -> Self {
-        let high_score = quad_storage::LocalStorage::default()
-            .get("high_score")
-            .unwrap_or("0".to_string())
-            .parse::<u32>()
-            .unwrap_or_default();
+        let high_scores = LEVELS.iter().map(Level::load_high_score).collect();
         let play = load_ase_texture(include_bytes!("../assets/play.ase"), None, None);
         let bsize = 0.2 * vec2(play.width(), play.height());
         let background = load_ase_texture(include_bytes!("../assets/background.ase"), None, None);
-        dbg!(background.width());
         let size = (background.width(), background.height());
         Self {
-            high_score,
+            high_scores,
+            replays: LEVELS.iter().map(Level::load_replay).collect(),
+            imported: None,
+            importing: false,
+            status: None,
+            watch: false,
+            selected_level: 0,
+            seed_input: String::new(),
+            editing_seed: false,
+            seed_too_big: false,
+            show_controls: false,
+            rebinding: None,
+            ui: Ui::default(),
             animation_clock: 0.0,
             current_animation: None,
             play: false,
@@ -923,8 +846,12 @@ impl Menu {
             size,
         }
     }
-    async fn update(&mut self) {
+    async fn update(&mut self, controls: &mut Controls) {
         let sf = (screen_width() / self.size.0).min(screen_height() / self.size.1);
+        // Typing a seed or a key to bind mustn't also move the focus around.
+        let taking_keys = self.editing_seed || self.rebinding.is_some();
+        self.ui
+            .begin(sf, (!taking_keys).then_some(&*controls), true);
 
         draw_texture_ex(
             &self.background,
@@ -939,16 +866,13 @@ impl Menu {
                 ..Default::default()
             },
         );
-        draw_texture_ex(
-            &self.button.texture,
-            self.button.rect.x * sf,
-            self.button.rect.y * sf,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(vec2(self.button.rect.w, self.button.rect.h) * sf),
-                ..Default::default()
-            },
-        );
+        if self.show_controls {
+            self.controls(sf, &mut controls.bindings);
+            return;
+        }
+        if self.ui.image_button(&self.button.texture, self.button.rect) {
+            self.play = true;
+        }
         let cat_pos = vec2(100.0, 82.0);
         let draw_still = || {
             let texture = &self.cat[0].0[0].0;
@@ -968,7 +892,7 @@ impl Menu {
             );
         };
         draw_text(
-            &format!("High score: {}", self.high_score),
+            &format!("High score: {}", self.high_scores[self.selected_level]),
             10.0 * sf,
             (self.background.height() - 10.0) * sf,
             20.0 * sf,
@@ -1014,53 +938,298 @@ impl Menu {
         } else {
             self.animation_timer -= get_frame_time();
         }
-        let mouse_pos = mouse_position();
-        let mouse_pos = (mouse_pos.0 / sf, mouse_pos.1 / sf);
-        if self.button.is_clicked(mouse_pos) {
-            self.play = true;
+        self.level_picker(sf);
+        self.seed_field();
+        self.replay_buttons(sf);
+        if self
+            .ui
+            .text_button("Controls", vec2(self.size.0 - 50.0, 14.0), 12.0)
+        {
+            self.show_controls = true;
+            self.ui.focus_first();
+        }
+    }
+    fn level_picker(&mut self, sf: f32) {
+        let font_size = 12.0;
+        let center = self.button.rect.x + self.button.rect.w / 2.0;
+        let y = self.button.rect.y + self.button.rect.h + 14.0;
+        let name = LEVELS[self.selected_level].name;
+        let name_width = measure_text(name, None, (font_size * sf) as u16, 1.0).width / sf;
+        draw_text(
+            name,
+            (center - name_width / 2.0) * sf,
+            y * sf,
+            font_size * sf,
+            WHITE,
+        );
+        if self
+            .ui
+            .text_button("<", vec2(self.button.rect.x, y), font_size)
+        {
+            self.selected_level = (self.selected_level + LEVELS.len() - 1) % LEVELS.len();
+        }
+        let next = vec2(self.button.rect.x + self.button.rect.w - font_size / 2.0, y);
+        if self.ui.text_button(">", next, font_size) {
+            self.selected_level = (self.selected_level + 1) % LEVELS.len();
+        }
+    }
+    /// The typed seed, if any. Activating it starts editing, and Enter, Escape or a click
+    /// stops.
+    fn seed_field(&mut self) {
+        if self.editing_seed {
+            while let Some(character) = get_char_pressed() {
+                if !character.is_ascii_digit() {
+                    continue;
+                }
+                // Digits that would take the seed past u64::MAX are refused.
+                let typed = format!("{}{character}", self.seed_input);
+                self.seed_too_big = typed.parse::<u64>().is_err();
+                if !self.seed_too_big {
+                    self.seed_input = typed;
+                }
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                self.seed_input.pop();
+                self.seed_too_big = false;
+            }
+            if is_key_pressed(KeyCode::Enter)
+                || is_key_pressed(KeyCode::Escape)
+                || is_mouse_button_pressed(MouseButton::Left)
+            {
+                self.editing_seed = false;
+                self.seed_too_big = false;
+            }
+        }
+        let text = if self.seed_too_big {
+            format!("Seed: {}_ (too big)", self.seed_input)
+        } else if self.editing_seed {
+            format!("Seed: {}_", self.seed_input)
+        } else if self.seed_input.is_empty() {
+            "Seed: random".to_string()
+        } else {
+            format!("Seed: {}", self.seed_input)
+        };
+        let y = self.button.rect.y + self.button.rect.h + 25.0;
+        if self
+            .ui
+            .text_button(&text, vec2(self.button.rect.x, y), 12.0)
+        {
+            self.editing_seed = !self.editing_seed;
+            // Characters typed before now have queued up unread.
+            clear_input_queue();
         }
     }
+    /// "Watch replay" and "Share", shown when the selected level has a recorded run, and
+    /// "Import".
+    fn replay_buttons(&mut self, sf: f32) {
+        let y = self.button.rect.y + self.button.rect.h + 36.0;
+        let level = &LEVELS[self.selected_level];
+        if let Some(replay) = &self.replays[self.selected_level] {
+            if self
+                .ui
+                .text_button("Watch replay", vec2(self.button.rect.x, y), 12.0)
+            {
+                self.watch = true;
+            }
+            if self.ui.text_button("Share", vec2(85.0, y), 12.0) {
+                let name = Replay::file_name(level.id);
+                self.status = Some(match file::save(&name, &replay.to_string()) {
+                    Ok(()) => format!("Saved {name}"),
+                    Err(err) => format!("Could not save {name}: {err}"),
+                });
+            }
+        }
+        if self.ui.text_button("Import", vec2(118.0, y), 12.0) {
+            self.importing = true;
+            file::open(&Replay::file_name(level.id));
+        }
+        if self.importing
+            && let Some(text) = file::opened()
+        {
+            self.importing = false;
+            let replay = text.and_then(|text| {
+                let replay: Replay = text.parse().map_err(|err: ReplayError| err.to_string())?;
+                let level = LEVELS
+                    .iter()
+                    .find(|f| f.id == replay.level)
+                    .ok_or_else(|| ReplayError::Level(replay.level.clone()).to_string())?;
+                replay.check(level).map_err(|err| err.to_string())?;
+                Ok(replay)
+            });
+            match replay {
+                Ok(replay) => {
+                    self.status = None;
+                    self.imported = Some(replay);
+                }
+                Err(err) => self.status = Some(format!("Could not import: {err}")),
+            }
+        }
+        if let Some(status) = &self.status {
+            draw_text(status, 10.0 * sf, 14.0 * sf, 10.0 * sf, WHITE);
+        }
+    }
+    /// Lists the keys of every action. Activating one waits for a new key, or Backspace
+    /// to clear it.
+    fn controls(&mut self, sf: f32, bindings: &mut Bindings) {
+        draw_rectangle(
+            0.0,
+            0.0,
+            self.size.0 * sf,
+            self.size.1 * sf,
+            BLACK.with_alpha(0.8),
+        );
+        // Before the buttons, so the key that started rebinding isn't taken as the new one.
+        // Escape cancels and keeps the old key. Only Reset puts Escape back on Pause.
+        if let Some((action, slot)) = self.rebinding
+            && let Some(key) = get_last_key_pressed()
+        {
+            if key != KeyCode::Escape {
+                if key == KeyCode::Backspace {
+                    bindings.bind(action, slot, None);
+                } else if input::is_bindable(key) {
+                    bindings.bind(action, slot, Some(key));
+                }
+                bindings.save();
+            }
+            self.rebinding = None;
+        }
+        let font_size = 10.0;
+        draw_text("Controls", 20.0 * sf, 22.0 * sf, 16.0 * sf, WHITE);
+        for (row, action) in Action::ALL.into_iter().enumerate() {
+            let y = 40.0 + row as f32 * 14.0;
+            draw_text(action.name(), 20.0 * sf, y * sf, font_size * sf, WHITE);
+            for (slot, key) in bindings.keys(action).into_iter().enumerate() {
+                let text = if self.rebinding == Some((action, slot)) {
+                    "...".to_string()
+                } else {
+                    key.map_or("-".to_string(), input::key_name)
+                };
+                let pos = vec2(90.0 + slot as f32 * 50.0, y);
+                if self.ui.text_button(&text, pos, font_size) {
+                    self.rebinding = Some((action, slot));
+                }
+            }
+        }
+        if self.ui.text_button("Reset", vec2(20.0, 140.0), font_size) {
+            *bindings = Bindings::default();
+            bindings.save();
+            self.rebinding = None;
+        }
+        if self.ui.text_button("Back", vec2(150.0, 140.0), font_size) {
+            self.show_controls = false;
+            self.rebinding = None;
+            self.ui.focus_first();
+        }
+    }
+    fn seed(&self) -> Option<u64> {
+        self.seed_input.parse().ok()
+    }
+}
+/// A seed for players who didn't pick one.
+fn random_seed() -> u64 {
+    (miniquad::date::now() * 1000.0) as u64
 }
-struct GameManager<'a> {
+struct GameManager {
+    controls: Controls,
     menu: Menu,
-    game: Option<Game<'a>>,
+    game: Option<Game>,
     state: State,
 }
-impl<'a> GameManager<'a> {
+impl GameManager {
     fn new() -> Self {
         Self {
             state: State::Menu,
             game: None,
+            controls: Controls::load(),
             menu: Menu::new(),
         }
     }
     async fn update(&mut self) {
+        self.controls.gamepads.update();
+        let buttons = match self.state {
+            State::Game | State::Paused => vec![(Action::Pause, input::pause_button())],
+            State::Menu => Vec::new(),
+        };
+        self.controls.touch.update(buttons);
+        // Taken every frame, so switching away in the menu doesn't pause the next round.
+        let lost_focus = focus::lost();
         match self.state {
             State::Game => {
                 let game = self.game.as_mut().unwrap();
                 if game.go_to_menu {
-                    self.state = State::Menu;
-                    self.menu = Menu::new();
-                    if game.kills > self.menu.high_score {
-                        self.menu.high_score = game.kills;
-                        let mut storage = quad_storage::LocalStorage::default();
-                        storage.set("high_score", &game.kills.to_string());
-                    }
-                    self.game = None;
+                    self.end_round();
+                } else if game.is_running()
+                    && (self.controls.is_pressed(Action::Pause) || lost_focus)
+                {
+                    self.state = State::Paused;
+                    game.ui.focus_first();
+                    game.pause_menu(&self.controls);
                 } else {
-                    game.update().await;
+                    game.update(&self.controls).await;
+                }
+            }
+            State::Paused => {
+                let game = self.game.as_mut().unwrap();
+                let choice = if self.controls.is_pressed(Action::Pause) {
+                    Some(PauseChoice::Resume)
+                } else {
+                    game.pause_menu(&self.controls)
+                };
+                match choice {
+                    Some(PauseChoice::Resume) => self.state = State::Game,
+                    Some(PauseChoice::Restart) => {
+                        self.state = State::Game;
+                        self.game = Some(match game.playback {
+                            Some(_) => Game::watch(game.level, game.replay.clone()),
+                            None => Game::new(game.level, game.world.seed),
+                        });
+                    }
+                    Some(PauseChoice::Quit) => self.end_round(),
+                    None => {}
                 }
             }
             State::Menu => {
-                if self.menu.play {
+                if let Some(replay) = self.menu.imported.take() {
+                    let level = LEVELS.iter().find(|f| f.id == replay.level).unwrap();
+                    self.state = State::Game;
+                    self.game = Some(Game::watch(level, replay));
+                } else if self.menu.watch {
+                    self.menu.watch = false;
+                    let level = &LEVELS[self.menu.selected_level];
+                    if let Some(replay) = self.menu.replays[self.menu.selected_level].clone() {
+                        self.state = State::Game;
+                        self.game = Some(Game::watch(level, replay));
+                    }
+                } else if self.menu.play {
+                    self.menu.play = false;
                     self.state = State::Game;
-                    self.game = Some(Game::new())
+                    let seed = self.menu.seed().unwrap_or_else(random_seed);
+                    self.game = Some(Game::new(&LEVELS[self.menu.selected_level], seed))
                 } else {
-                    self.menu.update().await
+                    self.menu.update(&mut self.controls).await
                 }
             }
         }
     }
+    /// Goes back to the menu, keeping the round's score if it was finished and beat the
+    /// high score.
+    fn end_round(&mut self) {
+        self.state = State::Menu;
+        let Some(game) = self.game.take() else {
+            return;
+        };
+        let level = LEVELS.iter().position(|f| f.id == game.level.id).unwrap();
+        if game.playback.is_none()
+            && game.world.is_over()
+            && game.world.kills > self.menu.high_scores[level]
+        {
+            self.menu.high_scores[level] = game.world.kills;
+            game.level.save_high_score(game.world.kills);
+            game.level.save_replay(&game.replay);
+            game.level.save_ghost(&game.ghost);
+            self.menu.replays[level] = Some(game.replay);
+        }
+    }
 }
 fn conf() -> Conf {
     Conf {
@@ -1073,7 +1242,7 @@ fn conf() -> Conf {
 #[macroquad::main(conf)]
 async fn main() {
     let mut game = GameManager::new();
-    rand::srand(get_time() as u64);
+    rand::srand(random_seed());
     loop {
         game.update().await;
         next_frame().await;
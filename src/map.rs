@@ -0,0 +1,369 @@
+//! The tile grid a round is played on, built from a Tiled map.
+use crate::{
+    collision::{self, Hit, TileShape},
+    tiled::{Flip, Properties, PropertyValue, TileLayer, TileMap, TiledError},
+};
+use macroquad::math::{Rect, Vec2, vec2};
+use std::{collections::HashMap, f32::consts::FRAC_PI_4};
+
+pub const MAP_SCALE_FACTOR: f32 = 3.0;
+/// Size of a map tile in world space.
+pub const TILE_SIZE: f32 = 16.0 * MAP_SCALE_FACTOR;
+/// Path cost of moving one tile straight, and diagonally.
+pub const STEP: u32 = 10;
+pub const DIAGONAL_STEP: u32 = 14;
+#[derive(Debug)]
+pub struct Layer {
+    /// Drawn after the mice and the cat, from the `above_actors` layer property.
+    pub above_actors: bool,
+    /// Hidden layers still collide and set tile properties, they just aren't drawn.
+    pub visible: bool,
+}
+impl Layer {
+    pub fn new(layer: &TileLayer) -> Self {
+        Self {
+            above_actors: layer.properties.bool("above_actors").unwrap_or(false),
+            visible: layer.visible,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy)]
+pub struct TileTexture {
+    pub coord: (u32, u32),
+    pub flip: Flip,
+    /// Index into `Map::layers`.
+    pub layer: usize,
+    /// Index into `Map::tile_animations` for animated tiles.
+    pub animation: Option<usize>,
+}
+/// Texture coordinates and durations in milliseconds of each frame, plus the total duration.
+pub type TileAnimation = (Vec<((u32, u32), u32)>, u32);
+#[derive(Debug)]
+pub struct Tile {
+    /// Ordered from the bottom layer up.
+    pub textures: Vec<TileTexture>,
+    /// Set when any tile stacked here has the `solid` property.
+    pub collision: bool,
+    /// The solid part of a colliding tile, from the `shape` property of its solid tiles.
+    pub shape: TileShape,
+    /// Properties of every tile stacked here, with upper layers taking precedence.
+    pub properties: Properties,
+}
+impl Tile {
+    /// Multiplier applied to movement speed. `slow` is either a bool, which halves
+    /// speed, or the fraction of speed taken away.
+    pub fn speed_factor(&self) -> f32 {
+        let slow = match self.properties.bool("slow") {
+            Some(slow) => {
+                if slow {
+                    0.5
+                } else {
+                    0.0
+                }
+            }
+            None => self.properties.float("slow").unwrap_or(0.0) as f32,
+        };
+        1.0 - slow.clamp(0.0, 1.0)
+    }
+}
+/// Properties levels can set that no rule reads yet.
+#[allow(dead_code)]
+impl Tile {
+    pub fn is_hiding_spot(&self) -> bool {
+        self.properties.bool("hiding_spot").unwrap_or(false)
+    }
+    pub fn damage(&self) -> f32 {
+        self.properties.float("damage").unwrap_or(0.0) as f32
+    }
+    /// Name of the sound to play when walking on this tile.
+    pub fn footstep_sound(&self) -> Option<&str> {
+        self.properties.string("footstep_sound")
+    }
+}
+fn load_tilemap(tilemap: &str, tileset: &str) -> Result<Map, TiledError> {
+    let map = TileMap::parse(tilemap, |source| {
+        source.ends_with("spritesheet.tsx").then_some(tileset)
+    })?;
+    let area = if map.infinite {
+        map.used_bounds().unwrap_or((0, 0, -1, -1))
+    } else {
+        (0, 0, map.width as i32 - 1, map.height as i32 - 1)
+    };
+    let mut tiles: Vec<Tile> =
+        Vec::with_capacity(((area.2 + 1 - area.0) * (area.3 + 1 - area.1)).max(0) as usize);
+    let mut tile_animations: Vec<TileAnimation> = Vec::new();
+    let mut animation_ids: HashMap<(u32, u32), usize> = HashMap::new();
+
+    for y in area.1..area.3 + 1 {
+        for x in area.0..area.2 + 1 {
+            let mut tile = Tile {
+                textures: vec![],
+                collision: false,
+                shape: TileShape::Empty,
+                properties: Properties::default(),
+            };
+            for (layer_index, layer) in map.layers.iter().enumerate() {
+                let gid = layer.get(x, y);
+                if gid.is_empty() {
+                    continue;
+                }
+                let (tileset, id) = map.tileset_for(gid)?;
+                let mut animation = None;
+                if let Some(data) = tileset.tileset.tiles.get(&id) {
+                    if data.properties.bool("solid").unwrap_or(false) {
+                        tile.collision = true;
+                        let shape = match data.properties.get("shape") {
+                            Some(PropertyValue::String(name)) => {
+                                TileShape::from_name(name).unwrap_or(TileShape::Full)
+                            }
+                            _ => TileShape::Full,
+                        };
+                        // Differently shaped solid tiles stacked on each other block the
+                        // whole cell.
+                        tile.shape = match tile.shape {
+                            TileShape::Empty => shape,
+                            current if current == shape => shape,
+                            _ => TileShape::Full,
+                        };
+                    }
+                    tile.properties.merge(&data.properties);
+                    if !data.animation.is_empty() {
+                        let index =
+                            *animation_ids
+                                .entry((tileset.first_gid, id))
+                                .or_insert_with(|| {
+                                    tile_animations.push((
+                                        data.animation
+                                            .iter()
+                                            .map(|f| {
+                                                (
+                                                    tileset.tileset.texture_coord(f.tile_id),
+                                                    f.duration,
+                                                )
+                                            })
+                                            .collect(),
+                                        data.animation.iter().map(|f| f.duration).sum(),
+                                    ));
+                                    tile_animations.len() - 1
+                                });
+                        animation = Some(index);
+                    }
+                }
+                tile.textures.push(TileTexture {
+                    coord: tileset.tileset.texture_coord(id),
+                    flip: gid.flip(),
+                    layer: layer_index,
+                    animation,
+                });
+            }
+            tiles.push(tile);
+        }
+    }
+    let mut result = Map {
+        tiles,
+        width: (area.2 + 1 - area.0) as u32,
+        layers: map.layers.iter().map(Layer::new).collect(),
+        tile_animations,
+        player_start: None,
+        spawn_regions: Vec::new(),
+        mouse_holes: Vec::new(),
+        exit_zones: Vec::new(),
+    };
+    // Objects are placed in map pixels relative to tile (0, 0), while the tile grid
+    // starts at the top left of the used area.
+    let origin = vec2(area.0 as f32, area.1 as f32) * 16.0;
+    for object in map.objects.iter() {
+        let bounds = object.bounds();
+        let rect = Rect::new(
+            (bounds.0 - origin.x) * MAP_SCALE_FACTOR,
+            (bounds.1 - origin.y) * MAP_SCALE_FACTOR,
+            bounds.2 * MAP_SCALE_FACTOR,
+            bounds.3 * MAP_SCALE_FACTOR,
+        );
+        match object.kind() {
+            "player_start" => result.player_start = Some(rect.center()),
+            "mouse_spawn" => result.spawn_regions.push(SpawnRegion {
+                name: object.name.clone(),
+                rect,
+                weight: object.properties.float("weight").unwrap_or(1.0) as f32,
+            }),
+            "mouse_hole" => result.mouse_holes.push(rect.center()),
+            "exit" => result.exit_zones.push(rect),
+            _ => {}
+        }
+    }
+    Ok(result)
+}
+pub struct SpawnRegion {
+    /// The object's name in Tiled, which wave scripts pick regions by.
+    pub name: String,
+    pub rect: Rect,
+    pub weight: f32,
+}
+pub struct Map {
+    pub tiles: Vec<Tile>,
+    pub width: u32,
+    /// Every tile layer, hidden ones included, in draw order.
+    pub layers: Vec<Layer>,
+    pub tile_animations: Vec<TileAnimation>,
+    pub player_start: Option<Vec2>,
+    pub spawn_regions: Vec<SpawnRegion>,
+    /// Where mice hide from the cat.
+    pub mouse_holes: Vec<Vec2>,
+    /// Ways off the map. Any mouse that walks into one is gone.
+    pub exit_zones: Vec<Rect>,
+}
+impl Map {
+    pub fn new(tilemap: &str) -> Result<Self, TiledError> {
+        load_tilemap(tilemap, include_str!("../assets/spritesheet.tsx"))
+    }
+    /// Grid position of the tile containing a world position. May lie outside the map.
+    pub fn world_to_tile(world_pos: Vec2) -> (i32, i32) {
+        let pos = (world_pos / TILE_SIZE).floor();
+        (pos.x as i32, pos.y as i32)
+    }
+    /// World position of a grid position's top left corner.
+    pub fn tile_to_world(x: i32, y: i32) -> Vec2 {
+        vec2(x as f32, y as f32) * TILE_SIZE
+    }
+    fn tile_index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height() as i32 {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+    pub fn tile(&self, x: i32, y: i32) -> Option<&Tile> {
+        self.tile_index(x, y).map(|f| &self.tiles[f])
+    }
+    /// Index of the tile containing a world position, if it is inside the map.
+    pub fn index_at(&self, world_pos: Vec2) -> Option<usize> {
+        let (x, y) = Self::world_to_tile(world_pos);
+        self.tile_index(x, y)
+    }
+    pub fn tile_at(&self, world_pos: Vec2) -> Option<&Tile> {
+        let (x, y) = Self::world_to_tile(world_pos);
+        self.tile(x, y)
+    }
+    /// Whether a world position is in the solid part of a tile. Everything outside the
+    /// map is solid.
+    pub fn is_solid(&self, world_pos: Vec2) -> bool {
+        let (x, y) = Self::world_to_tile(world_pos);
+        let local = world_pos / TILE_SIZE - vec2(x as f32, y as f32);
+        self.shape_at(x, y).contains(local.x, local.y)
+    }
+    pub fn tile_pos(&self, index: usize) -> Vec2 {
+        Self::tile_to_world(
+            (index as u32 % self.width) as i32,
+            (index as u32 / self.width) as i32,
+        )
+    }
+    /// Whether paths may cross a tile. Half and slope tiles are walkable, as `move_aabb`
+    /// slides things around their solid part; only full walls and tiles without a floor
+    /// are not.
+    pub fn is_open(&self, index: usize) -> bool {
+        let tile = &self.tiles[index];
+        tile.shape != TileShape::Full && !tile.textures.is_empty()
+    }
+    /// Whether a tile is walkable with no solid part at all.
+    pub fn is_clear(&self, index: usize) -> bool {
+        self.is_open(index) && self.tiles[index].shape == TileShape::Empty
+    }
+    /// The walkable tiles around a walkable tile, with the cost of stepping there. Diagonal
+    /// steps are only allowed when both tiles beside them are clear, so paths never cut
+    /// corners.
+    pub fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, u32)> + '_ {
+        let (x, y) = (
+            (index as u32 % self.width) as i32,
+            (index as u32 / self.width) as i32,
+        );
+        let at = move |dx, dy| self.tile_index(x + dx, y + dy);
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let tile = at(dx, dy).filter(|f| self.is_open(*f))?;
+            if dx != 0 && dy != 0 {
+                at(dx, 0).filter(|f| self.is_clear(*f))?;
+                at(0, dy).filter(|f| self.is_clear(*f))?;
+                return Some((tile, DIAGONAL_STEP));
+            }
+            Some((tile, STEP))
+        })
+    }
+    /// Turns something heading in `direction` from `centre` aside, towards the nearest
+    /// free heading, before it walks into a wall.
+    pub fn avoid_walls(&self, centre: Vec2, direction: Vec2) -> Vec2 {
+        let turns = [0.0, 1.0, -1.0, 2.0, -2.0, 3.0, -3.0, 4.0];
+        turns
+            .into_iter()
+            .map(|f| Vec2::from_angle(f * FRAC_PI_4).rotate(direction))
+            .find(|f| !self.is_solid(centre + *f * TILE_SIZE * 0.75))
+            .unwrap_or(direction)
+    }
+    /// Indices of the walkable tiles whose centre lies inside `rect`. A rect without
+    /// size selects the tile it sits on.
+    pub fn open_tiles_in(&self, rect: Rect) -> Vec<usize> {
+        (0..self.tiles.len())
+            .filter(|f| self.is_open(*f))
+            .filter(|f| {
+                let pos = self.tile_pos(*f);
+                let tile = Rect::new(pos.x, pos.y, TILE_SIZE, TILE_SIZE);
+                if rect.w == 0.0 && rect.h == 0.0 {
+                    tile.contains(rect.point())
+                } else {
+                    rect.contains(tile.center())
+                }
+            })
+            .collect()
+    }
+    /// Shape of the tile at a grid position, where outside the map is fully solid.
+    pub fn shape_at(&self, x: i32, y: i32) -> TileShape {
+        self.tile(x, y).map_or(TileShape::Full, |f| f.shape)
+    }
+    /// Moves a box in world space through the map, see `collision::move_aabb`.
+    pub fn move_aabb(&self, aabb: Rect, motion: Vec2) -> (Vec2, Hit) {
+        collision::move_aabb(aabb, motion, TILE_SIZE, |x, y| self.shape_at(x, y))
+    }
+    /// Where the cat starts, falling back to the first walkable tile when the map has
+    /// no `player_start` object or it was placed somewhere solid.
+    pub fn player_start(&self) -> Vec2 {
+        self.player_start
+            .filter(|f| !self.is_solid(*f))
+            .unwrap_or_else(|| {
+                (0..self.tiles.len())
+                    .find(|f| self.is_clear(*f))
+                    .map(|f| self.tile_pos(f) + Vec2::splat(TILE_SIZE / 2.0))
+                    .unwrap_or_default()
+            })
+    }
+    /// The frame an animated tile shows `time` seconds in, or its static texture.
+    pub fn current_coord(&self, texture: &TileTexture, time: f64) -> (u32, u32) {
+        let Some(animation) = texture.animation.map(|f| &self.tile_animations[f]) else {
+            return texture.coord;
+        };
+        if animation.1 == 0 {
+            return texture.coord;
+        }
+        let mut time = (time * 1000.0) as u64 % animation.1 as u64;
+        for frame in animation.0.iter() {
+            if time < frame.1 as u64 {
+                return frame.0;
+            }
+            time -= frame.1 as u64;
+        }
+        texture.coord
+    }
+    pub fn height(&self) -> u32 {
+        self.tiles.len() as u32 / self.width.max(1)
+    }
+}
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,109 @@
+//! Maps built from inline TMX with a tileset of partly solid tiles.
+use super::*;
+
+/// Floor, a full wall, a wall filling the top half and one filling the top left corner.
+const TILESET: &str = r#"<tileset name="shapes" tilewidth="16" tileheight="16" tilecount="4" columns="4">
+ <tile id="1">
+  <properties><property name="solid" type="bool" value="true"/></properties>
+ </tile>
+ <tile id="2">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+   <property name="shape" value="half_top"/>
+  </properties>
+ </tile>
+ <tile id="3">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+   <property name="shape" value="slope_top_left"/>
+  </properties>
+ </tile>
+</tileset>"#;
+
+/// A 3x3 room with a half wall on tile (1, 1), a full wall on (2, 1) and a slope on
+/// (0, 2).
+const ROOM: &str = r#"<map orientation="orthogonal" width="3" height="3" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" source="spritesheet.tsx"/>
+ <layer id="1" name="floor" width="3" height="3">
+  <data encoding="csv">1,1,1,1,1,1,1,1,1</data>
+ </layer>
+ <layer id="2" name="collision" width="3" height="3">
+  <data encoding="csv">0,0,0,0,3,2,4,0,0</data>
+ </layer>
+</map>"#;
+
+fn room() -> Map {
+    load_tilemap(ROOM, TILESET).unwrap()
+}
+/// World position of a point given in tile units.
+fn at(x: f32, y: f32) -> Vec2 {
+    vec2(x, y) * TILE_SIZE
+}
+
+#[test]
+fn solid_points_follow_the_tile_shape() {
+    let map = room();
+    assert!(map.is_solid(at(1.5, 1.25)));
+    assert!(!map.is_solid(at(1.5, 1.75)));
+    assert!(map.is_solid(at(2.5, 1.75)));
+    assert!(map.is_solid(at(0.25, 2.25)));
+    assert!(!map.is_solid(at(0.75, 2.75)));
+    assert!(!map.is_solid(at(0.5, 0.5)));
+    assert!(map.is_solid(at(-0.5, 0.5)));
+}
+
+#[test]
+fn partial_tiles_are_open_but_not_clear() {
+    let map = room();
+    let index = |x, y| map.tile_index(x, y).unwrap();
+    assert!(map.is_open(index(1, 1)) && !map.is_clear(index(1, 1)));
+    assert!(map.is_open(index(0, 2)) && !map.is_clear(index(0, 2)));
+    assert!(!map.is_open(index(2, 1)));
+    assert!(map.is_clear(index(0, 0)));
+}
+
+#[test]
+fn paths_cross_partial_tiles_without_cutting_their_corners() {
+    let map = room();
+    let index = |x, y| map.tile_index(x, y).unwrap();
+    let neighbours: Vec<_> = map.neighbours(index(1, 0)).collect();
+    assert!(neighbours.contains(&(index(1, 1), STEP)));
+    // Both diagonals down from (1, 0) pass the corner of the half wall, and the one to
+    // the right ends on the full wall.
+    assert!(!neighbours.iter().any(|f| f.0 == index(0, 1)));
+    assert!(!neighbours.iter().any(|f| f.0 == index(2, 1)));
+
+    // Stepping diagonally onto a partial tile is fine when the tiles beside are clear.
+    let neighbours: Vec<_> = map.neighbours(index(0, 0)).collect();
+    assert!(neighbours.contains(&(index(1, 1), DIAGONAL_STEP)));
+}
+
+#[test]
+fn hidden_layers_still_collide() {
+    let tmx = ROOM.replace(r#"name="collision""#, r#"name="collision" visible="0""#);
+    let map = load_tilemap(&tmx, TILESET).unwrap();
+    assert!(!map.layers[1].visible);
+    assert!(map.is_solid(at(2.5, 1.5)));
+    assert!(!map.is_open(map.tile_index(2, 1).unwrap()));
+    assert_eq!(map.shape_at(1, 1), TileShape::HalfTop);
+}
+
+#[test]
+fn tile_properties_are_typed() {
+    let tileset = TILESET.replace(
+        r#"<tile id="1">"#,
+        r#"<tile id="0">
+  <properties>
+   <property name="hiding_spot" type="bool" value="true"/>
+   <property name="damage" type="int" value="2"/>
+   <property name="footstep_sound" value="grass"/>
+  </properties>
+ </tile>
+ <tile id="1">"#,
+    );
+    let map = load_tilemap(ROOM, &tileset).unwrap();
+    let tile = &map.tiles[map.tile_index(0, 0).unwrap()];
+    assert!(tile.is_hiding_spot());
+    assert_eq!(tile.damage(), 2.0);
+    assert_eq!(tile.footstep_sound(), Some("grass"));
+}
@@ -0,0 +1,148 @@
+//! Recorded rounds. A round is fully determined by its level, seed and the input of
+//! every tick, so that is all a replay stores.
+use crate::{
+    level::Level,
+    sim::{Input, RULES_VERSION, TICK, World},
+};
+use macroquad::math::vec2;
+use std::{fmt, str::FromStr};
+
+const HEADER: &str = "catscapade-replay 2";
+
+/// Each axis of the input stored in a byte.
+fn encode_axis(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+fn decode_axis(value: i8) -> f32 {
+    value as f32 / 127.0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub level: String,
+    pub seed: u64,
+    /// Runs of identical input as `(x, y, ticks)`.
+    runs: Vec<(i8, i8, u32)>,
+}
+impl Replay {
+    pub fn new(level: &str, seed: u64) -> Self {
+        Self {
+            level: level.to_string(),
+            seed,
+            runs: Vec::new(),
+        }
+    }
+    /// Adds the input of the next tick. Returns it as it will be played back, which the
+    /// live round has to use too so both play out the same.
+    pub fn record(&mut self, input: Input) -> Input {
+        let x = encode_axis(input.direction.x);
+        let y = encode_axis(input.direction.y);
+        match self.runs.last_mut() {
+            Some(run) if run.0 == x && run.1 == y && run.2 < u32::MAX => run.2 += 1,
+            _ => self.runs.push((x, y, 1)),
+        }
+        Input {
+            direction: vec2(decode_axis(x), decode_axis(y)),
+        }
+    }
+    /// Makes sure the replay belongs to `level` and covers exactly the ticks its round
+    /// lasts, by playing it through.
+    pub fn check(&self, level: &Level) -> Result<(), ReplayError> {
+        if self.level != level.id {
+            return Err(ReplayError::Level(self.level.clone()));
+        }
+        let mut world = World::new(level, self.seed)
+            .unwrap_or_else(|err| panic!("could not load tilemap for {}: {err}", level.name));
+        let mut inputs = self.inputs();
+        while !world.is_over() {
+            world.update(TICK, inputs.next().ok_or(ReplayError::Length)?);
+        }
+        match inputs.next() {
+            Some(_) => Err(ReplayError::Length),
+            None => Ok(()),
+        }
+    }
+    /// Name of the file a replay of `level` is shared as.
+    pub fn file_name(level: &str) -> String {
+        format!("catscapade-{level}.replay")
+    }
+    /// The input of every recorded tick, in order.
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        self.runs.iter().flat_map(|f| {
+            let input = Input {
+                direction: vec2(decode_axis(f.0), decode_axis(f.1)),
+            };
+            std::iter::repeat_n(input, f.2 as usize)
+        })
+    }
+}
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "rules {RULES_VERSION}")?;
+        writeln!(f, "{}", self.level)?;
+        writeln!(f, "{}", self.seed)?;
+        for run in self.runs.iter() {
+            writeln!(f, "{} {} {}", run.0, run.1, run.2)?;
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    Invalid,
+    /// Recorded under other rules, with their version.
+    Rules(u32),
+    /// Recorded on another level, with its id.
+    Level(String),
+    /// The input runs out before the round is over, or goes on after it.
+    Length,
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "not a replay"),
+            Self::Rules(version) => write!(
+                f,
+                "recorded under rules {version}, this game has rules {RULES_VERSION}"
+            ),
+            Self::Level(level) => write!(f, "recorded on another level ({level})"),
+            Self::Length => write!(f, "does not match the length of the round"),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+impl FromStr for Replay {
+    type Err = ReplayError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(ReplayError::Invalid);
+        }
+        let rules = lines
+            .next()
+            .and_then(|f| f.strip_prefix("rules "))
+            .and_then(|f| f.parse().ok())
+            .ok_or(ReplayError::Invalid)?;
+        if rules != RULES_VERSION {
+            return Err(ReplayError::Rules(rules));
+        }
+        let level = lines.next().ok_or(ReplayError::Invalid)?.to_string();
+        let seed = lines
+            .next()
+            .and_then(|f| f.parse().ok())
+            .ok_or(ReplayError::Invalid)?;
+        let runs = lines
+            .filter(|f| !f.is_empty())
+            .map(|line| {
+                let mut values = line.split(' ');
+                let mut next = || values.next().ok_or(ReplayError::Invalid);
+                let x = next()?.parse().map_err(|_| ReplayError::Invalid)?;
+                let y = next()?.parse().map_err(|_| ReplayError::Invalid)?;
+                let ticks = next()?.parse().map_err(|_| ReplayError::Invalid)?;
+                Ok((x, y, ticks))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { level, seed, runs })
+    }
+}
@@ -0,0 +1,498 @@
+//! The rules of a round. Nothing in here draws or reads input, so a `World` can be
+//! stepped without a window.
+use crate::{
+    flow_field::FlowField,
+    level::{Level, Wave},
+    map::{Map, STEP, TILE_SIZE, Tile},
+    species::{SPECIES, Species},
+    tiled::TiledError,
+};
+use asefile::AsepriteFile;
+use macroquad::{
+    math::{Rect, Vec2, vec2},
+    rand::RandGenerator,
+};
+use nanoserde::DeJson;
+use std::{f32::consts::PI, sync::LazyLock};
+
+const CAT_SPEED: f32 = 200.0;
+/// Simulation steps per second, independent of the frame rate.
+pub const TICK_RATE: f32 = 60.0;
+pub const TICK: f32 = 1.0 / TICK_RATE;
+/// Bumped whenever a change to the rules would make recorded replays play out differently.
+pub const RULES_VERSION: u32 = 3;
+/// How much of the world the camera shows, centred on the cat.
+pub const VIEW_SIZE: Vec2 = Vec2 { x: 160.0, y: 160.0 };
+/// How much farther from the cat a fleeing mouse values each step, see
+/// `FlowField::flee`. Above one, so running past the cat to open space can win over
+/// backing into a corner.
+const FLEE: f32 = 1.2;
+/// How far a mouse has to run before it can escape, so one that appears on a hole or in
+/// an exit doesn't vanish straight away.
+const ESCAPE_AFTER: f32 = TILE_SIZE;
+
+/// Size of the first frame of an Aseprite file, read without creating a texture.
+pub fn sprite_size(bytes: &[u8]) -> Vec2 {
+    let file = AsepriteFile::read(bytes).unwrap();
+    vec2(file.width() as f32, file.height() as f32)
+}
+static CAT_SIZE: LazyLock<Vec2> =
+    LazyLock::new(|| sprite_size(include_bytes!("../assets/cat.ase")));
+
+/// What the player wants to do during one tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Input {
+    /// Each axis from -1 to 1, with y pointing down.
+    pub direction: Vec2,
+}
+pub struct Cat {
+    pub pos: Vec2,
+    /// Position before the last tick, for interpolating between ticks when drawing.
+    pub prev_pos: Vec2,
+    pub size: Vec2,
+    direction: Vec2,
+    /// How hard the stick was pushed last, from 0 to 1. Keys always push all the way.
+    throttle: f32,
+    /// Distance moved in the last tick as a fraction of full speed.
+    pub speed: f32,
+    /// Facing, as a sprite rotation.
+    pub rotation: f32,
+}
+impl Cat {
+    fn new(start: Vec2) -> Self {
+        let size = *CAT_SIZE;
+        Self {
+            rotation: 0.0,
+            pos: start - size / 2.0,
+            prev_pos: start - size / 2.0,
+            size,
+            direction: Vec2::ZERO,
+            throttle: 0.0,
+            speed: 0.0,
+        }
+    }
+    fn update(&mut self, map: &Map, input: Input, dt: f32) {
+        self.prev_pos = self.pos;
+        let direction = input.direction;
+        if direction != Vec2::ZERO {
+            self.rotation = 0.5 * PI + direction.y.atan2(direction.x);
+            self.throttle = direction.length().min(1.0);
+        }
+        self.direction += direction.normalize_or_zero();
+        let speed_factor = map
+            .tile_at(self.pos + self.size / 2.0)
+            .map_or(1.0, Tile::speed_factor);
+        let shrunk_collision = 4.0;
+        let hitbox = Rect::new(
+            self.pos.x + shrunk_collision,
+            self.pos.y + shrunk_collision,
+            self.size.x - shrunk_collision * 2.0,
+            self.size.y - shrunk_collision * 2.0,
+        );
+        let (moved, hit) = map.move_aabb(
+            hitbox,
+            self.direction.normalize_or_zero() * CAT_SPEED * self.throttle * speed_factor * dt,
+        );
+        self.pos += moved;
+        self.speed = moved.length() / (CAT_SPEED * dt);
+        if hit.horizontal() {
+            self.direction.x = 0.0;
+        }
+        if hit.vertical() {
+            self.direction.y = 0.0;
+        }
+
+        self.direction *= 0.8;
+        if self.direction.x.abs() < 0.3 && self.direction.y.abs() < 0.3 {
+            self.direction = Vec2::ZERO;
+        }
+    }
+    /// Where to draw the cat, `alpha` of the way from the previous tick to the last one.
+    pub fn render_pos(&self, alpha: f32) -> Vec2 {
+        self.prev_pos.lerp(self.pos, alpha)
+    }
+}
+/// How a kind of mouse reacts to the cat and to other mice.
+#[derive(Debug, Clone, Copy, PartialEq, DeJson)]
+pub struct Behaviour {
+    /// Path distance from the cat that scares the mouse. Walls block the scare.
+    pub scare_radius: f32,
+    /// Seconds the mouse keeps fleeing after the cat is out of range.
+    pub scare_time: f32,
+    /// Pull of the nearest mouse hole while fleeing, against the push of the cat. Zero
+    /// only looks for open space.
+    pub hole_bias: f32,
+    /// How close other mice can get before this one steers away, and how hard it does.
+    pub personal_space: f32,
+    pub separation: f32,
+    /// Seconds between new directions while wandering.
+    pub wander_interval: (f32, f32),
+}
+pub struct Mouse {
+    pub species: &'static Species,
+    /// The species' behaviour, which tests tune per mouse.
+    behaviour: Behaviour,
+    scare_timer: f32,
+    random_direction_cooldown: f32,
+    /// Distance run since spawning, see `ESCAPE_AFTER`.
+    travelled: f32,
+    pub pos: Vec2,
+    pub prev_pos: Vec2,
+    speed: f32,
+    pub size: Vec2,
+    pub direction: Vec2,
+}
+impl Mouse {
+    pub fn render_pos(&self, alpha: f32) -> Vec2 {
+        self.prev_pos.lerp(self.pos, alpha)
+    }
+}
+/// Index of a random entry from a list of weights, each picked in proportion to its
+/// weight. There must be at least one.
+fn pick_weighted(rng: &RandGenerator, weights: impl Iterator<Item = f32> + Clone) -> usize {
+    let mut pick = rng.gen_range(0.0, weights.clone().sum::<f32>());
+    let last = weights.clone().count() - 1;
+    weights
+        .into_iter()
+        .position(|f| {
+            pick -= f;
+            pick < 0.0
+        })
+        .unwrap_or(last)
+}
+/// Plays a level's wave script.
+struct Director {
+    waves: &'static [Wave],
+    /// Index of the next wave.
+    next: usize,
+    /// Seconds until the next wave, at normal difficulty.
+    clock: f32,
+    max_mice: usize,
+    ramp: f32,
+    time_limit: f32,
+}
+impl Director {
+    fn new(level: &Level) -> Self {
+        Self {
+            waves: level.waves,
+            next: 0,
+            clock: level.waves.first().map_or(0.0, |f| f.after),
+            max_mice: level.max_mice,
+            ramp: level.ramp,
+            time_limit: level.time_limit,
+        }
+    }
+    fn spawn_wave(
+        entities: &mut Vec<Mouse>,
+        map: &Map,
+        wave: &Wave,
+        count: usize,
+        allowed: impl Fn(usize) -> bool,
+        rng: &RandGenerator,
+    ) {
+        let mut regions: Vec<(f32, Vec<usize>)> = map
+            .spawn_regions
+            .iter()
+            .filter(|f| wave.regions.is_empty() || wave.regions.contains(&f.name.as_str()))
+            .map(|f| (f.weight, map.open_tiles_in(f.rect)))
+            .collect();
+        if regions.is_empty() {
+            regions.push((
+                1.0,
+                (0..map.tiles.len()).filter(|f| map.is_open(*f)).collect(),
+            ));
+        }
+        for region in regions.iter_mut() {
+            region.1.retain(|f| allowed(*f));
+        }
+        let species: Vec<(&Species, f32)> = if wave.species.is_empty() {
+            SPECIES.iter().map(|f| (f, f.spawn_weight)).collect()
+        } else {
+            wave.species
+                .iter()
+                .filter_map(|(name, weight)| {
+                    Some((SPECIES.iter().find(|f| f.name == *name)?, *weight))
+                })
+                .collect()
+        };
+        if species.iter().all(|f| f.1 <= 0.0) {
+            return;
+        }
+        for _ in 0..count {
+            regions.retain(|f| f.0 > 0.0 && !f.1.is_empty());
+            if regions.is_empty() {
+                break;
+            }
+            let index = pick_weighted(rng, regions.iter().map(|f| f.0));
+            let tiles = &mut regions[index].1;
+            let tile = tiles.swap_remove(rng.gen_range(0, tiles.len()));
+            let species = species[pick_weighted(rng, species.iter().map(|f| f.1))].0;
+            entities.push(Mouse {
+                species,
+                speed: species.speed,
+                behaviour: species.behaviour,
+                scare_timer: 0.0,
+                random_direction_cooldown: 0.0,
+                travelled: 0.0,
+                size: species.size(),
+                pos: map.tile_pos(tile),
+                prev_pos: map.tile_pos(tile),
+                direction: Vec2::ZERO,
+            });
+        }
+    }
+    /// Counts down to the next wave, with `time_left` in the round and `live` mice about.
+    /// Returns the wave when it is due, and how many mice it should spawn.
+    fn update(&mut self, live: usize, dt: f32, time_left: f32) -> Option<(&'static Wave, usize)> {
+        let progress = 1.0 - time_left / self.time_limit.max(f32::EPSILON);
+        let difficulty = 1.0 + self.ramp * progress.clamp(0.0, 1.0);
+        // A player who has caught everything gets the next wave sooner.
+        let hurry = if live == 0 { 2.0 } else { 1.0 };
+        self.clock -= dt * difficulty * hurry;
+        if self.clock > 0.0 || self.waves.is_empty() {
+            return None;
+        }
+        let wave = &self.waves[self.next];
+        let count = ((wave.count as f32 * difficulty).round() as usize)
+            .min(self.max_mice.saturating_sub(live));
+        self.next = (self.next + 1).min(self.waves.len() - 1);
+        // At most one wave per tick, even if a script has no gaps.
+        self.clock = self.waves[self.next].after.max(dt);
+        Some((wave, count))
+    }
+}
+/// Everything that changes during a round.
+pub struct World {
+    pub map: Map,
+    pub cat: Cat,
+    pub mice: Vec<Mouse>,
+    director: Director,
+    /// The seed `rng` started from. The same seed and inputs always play out the same.
+    pub seed: u64,
+    rng: RandGenerator,
+    /// Seconds left in the round.
+    pub timer: f32,
+    /// Ticks played so far.
+    pub ticks: u32,
+    pub kills: u32,
+    /// Mice that got away through a hole or an exit.
+    pub escapes: u32,
+    /// How close to the cat mice can spawn, and whether only out of view.
+    spawn_distance: f32,
+    spawn_off_screen: bool,
+    /// Tiles with a mouse hole on them.
+    hole_tiles: Vec<usize>,
+    /// Path cost to the nearest mouse hole or exit.
+    holes: FlowField,
+    /// The tile the cat was on when `from_cat` and `flee` were last worked out.
+    cat_tile: Option<usize>,
+    /// Path cost to the cat.
+    from_cat: FlowField,
+    flee: FlowField,
+}
+impl World {
+    pub fn new(level: &Level, seed: u64) -> Result<Self, TiledError> {
+        let map = Map::new(level.tilemap)?;
+        let rng = RandGenerator::new();
+        rng.srand(seed);
+        let hole_tiles: Vec<usize> = map
+            .mouse_holes
+            .iter()
+            .filter_map(|f| map.index_at(*f))
+            .collect();
+        let exits = map.exit_zones.iter().flat_map(|f| map.open_tiles_in(*f));
+        Ok(Self {
+            holes: FlowField::distances(&map, hole_tiles.iter().copied().chain(exits)),
+            hole_tiles,
+            escapes: 0,
+            spawn_distance: level.spawn_distance,
+            spawn_off_screen: level.spawn_off_screen,
+            cat_tile: None,
+            from_cat: FlowField::default(),
+            flee: FlowField::default(),
+            seed,
+            rng,
+            cat: Cat::new(map.player_start()),
+            map,
+            mice: Vec::new(),
+            director: Director::new(level),
+            timer: level.time_limit,
+            ticks: 0,
+            kills: 0,
+        })
+    }
+    /// Advances the round by one step of `dt` seconds. The timer stops at zero.
+    pub fn update(&mut self, dt: f32, input: Input) {
+        self.ticks += 1;
+        self.mouse_eatery();
+        self.mouse_behaviour(dt);
+        self.cat.update(&self.map, input, dt);
+        if let Some((wave, count)) = self.director.update(self.mice.len(), dt, self.timer) {
+            let allowed: Vec<bool> = (0..self.map.tiles.len())
+                .map(|f| self.can_spawn_on(f))
+                .collect();
+            Director::spawn_wave(
+                &mut self.mice,
+                &self.map,
+                wave,
+                count,
+                |tile| allowed[tile],
+                &self.rng,
+            );
+        }
+        if self.timer > 0.0 {
+            self.timer -= dt
+        }
+    }
+    /// The part of the world on screen, matching the camera that follows the cat.
+    pub fn view(&self) -> Rect {
+        Rect::new(
+            self.cat.pos.x - VIEW_SIZE.x / 2.0,
+            self.cat.pos.y - VIEW_SIZE.y / 2.0,
+            VIEW_SIZE.x,
+            VIEW_SIZE.y,
+        )
+    }
+    /// Whether a mouse may appear on a tile: clear of walls, far enough from the cat, off
+    /// screen if the level wants that, and somewhere the cat can get to.
+    fn can_spawn_on(&self, tile: usize) -> bool {
+        let pos = self.map.tile_pos(tile);
+        let rect = Rect::new(pos.x, pos.y, TILE_SIZE, TILE_SIZE);
+        let cat = self.cat.pos + self.cat.size / 2.0;
+        self.map.is_clear(tile)
+            && rect.center().distance(cat) >= self.spawn_distance
+            && !(self.spawn_off_screen && rect.overlaps(&self.view()))
+            && self.from_cat.cost(tile).is_some()
+    }
+    pub fn is_over(&self) -> bool {
+        self.timer <= 0.0
+    }
+    fn mouse_eatery(&mut self) {
+        self.mice.retain(|f| {
+            let collisions = [
+                (0.0, 0.0),
+                (f.size.x, 0.0),
+                (0.0, f.size.y),
+                (f.size.x, f.size.y),
+            ];
+            let mut collide = false;
+            for p in collisions {
+                if p.0 + f.pos.x >= self.cat.pos.x
+                    && p.0 + f.pos.x <= self.cat.pos.x + self.cat.size.x
+                    && f.pos.y + p.1 <= self.cat.pos.y + self.cat.size.y
+                    && f.pos.y + p.1 > self.cat.pos.y
+                {
+                    collide = true;
+                    self.kills += f.species.score;
+                    break;
+                }
+            }
+
+            !collide
+        })
+    }
+    fn mouse_behaviour(&mut self, dt: f32) {
+        let cat_centre = self.cat.pos + self.cat.size / 2.0;
+        let cat_tile = self.map.index_at(cat_centre);
+        if cat_tile != self.cat_tile {
+            self.cat_tile = cat_tile;
+            self.from_cat = FlowField::distances(&self.map, cat_tile);
+            self.flee = FlowField::flee(&self.map, &self.from_cat, FLEE);
+        }
+        let centres: Vec<Vec2> = self.mice.iter().map(|f| f.pos + f.size / 2.0).collect();
+        for index in 0..self.mice.len() {
+            let mouse = &mut self.mice[index];
+            mouse.prev_pos = mouse.pos;
+            let centre = centres[index];
+            let tile_index = self.map.index_at(centre);
+            let tile = tile_index.map(|f| &self.map.tiles[f]);
+            let behaviour = mouse.behaviour;
+            mouse.scare_timer = (mouse.scare_timer - dt).max(0.0);
+            let cat_distance = tile_index
+                .and_then(|f| self.from_cat.cost(f))
+                .map(|f| f as f32 / STEP as f32 * TILE_SIZE);
+            if cat_distance.is_some_and(|f| f < behaviour.scare_radius) {
+                mouse.scare_timer = behaviour.scare_time;
+            }
+            if mouse.scare_timer > 0.0 {
+                let direction = self.flee_direction(tile_index, centre, behaviour);
+                self.mice[index].direction = direction;
+            } else {
+                let mouse = &mut self.mice[index];
+                if mouse.random_direction_cooldown < 0.0 {
+                    mouse.direction =
+                        vec2(self.rng.gen_range(-1.0, 1.0), self.rng.gen_range(-1.0, 1.0))
+                            .normalize_or_zero();
+                    mouse.random_direction_cooldown = self
+                        .rng
+                        .gen_range(behaviour.wander_interval.0, behaviour.wander_interval.1);
+                } else {
+                    mouse.random_direction_cooldown -= dt;
+                }
+                mouse.direction = self.map.avoid_walls(centre, mouse.direction);
+            }
+            let mut push = Vec2::ZERO;
+            for (other, other_centre) in centres.iter().enumerate() {
+                let offset = centre - *other_centre;
+                let distance = offset.length();
+                if other != index && distance > 0.0 && distance < behaviour.personal_space {
+                    push += offset / distance * (1.0 - distance / behaviour.personal_space);
+                }
+            }
+            let mouse = &mut self.mice[index];
+            let steering = mouse.direction + push * behaviour.separation;
+            let speed_factor = tile.map_or(1.0, Tile::speed_factor);
+            let (moved, hit) = self.map.move_aabb(
+                Rect::new(mouse.pos.x, mouse.pos.y, mouse.size.x, mouse.size.y),
+                steering.normalize_or_zero() * mouse.speed * speed_factor * dt,
+            );
+            mouse.pos += moved;
+            mouse.travelled += moved.length();
+            if hit.horizontal() {
+                mouse.direction.x *= -1.0;
+            }
+            if hit.vertical() {
+                mouse.direction.y *= -1.0;
+            }
+        }
+        // Calm mice wander over holes and only go down them when running from the cat, but
+        // an exit leads off the map, so whatever walks into one has escaped. Either way, only
+        // once the mouse has run `ESCAPE_AFTER`.
+        let before = self.mice.len();
+        let (map, holes) = (&self.map, &self.hole_tiles);
+        self.mice.retain(|f| {
+            let centre = f.pos + f.size / 2.0;
+            let in_hole = f.scare_timer > 0.0
+                && map
+                    .index_at(centre)
+                    .is_some_and(|tile| holes.contains(&tile));
+            let in_exit = map.exit_zones.iter().any(|zone| zone.contains(centre));
+            f.travelled < ESCAPE_AFTER || !(in_hole || in_exit)
+        });
+        self.escapes += (before - self.mice.len()) as u32;
+    }
+    /// Which way a scared mouse on `tile` runs: towards the neighbouring tile where the
+    /// flee field and the pull of the holes add up lowest, or away from the cat when
+    /// there is no path at all.
+    fn flee_direction(&self, tile: Option<usize>, centre: Vec2, behaviour: Behaviour) -> Vec2 {
+        let score = |tile| {
+            let hole = self.holes.cost(tile).unwrap_or(0) as f32;
+            Some(self.flee.cost(tile)? as f32 + hole * behaviour.hole_bias)
+        };
+        let best = tile.and_then(|tile| {
+            std::iter::once(tile)
+                .chain(self.map.neighbours(tile).map(|f| f.0))
+                .filter_map(|f| Some((f, score(f)?)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+        });
+        match best {
+            // When the mouse is already on the safest tile, it keeps to the middle of it.
+            Some((target, _)) => (self.map.tile_pos(target) + Vec2::splat(TILE_SIZE / 2.0)
+                - centre)
+                .normalize_or_zero(),
+            None => (centre - (self.cat.pos + self.cat.size / 2.0)).normalize_or_zero(),
+        }
+    }
+}
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,488 @@
+//! Rounds played without a window, driven by scripted input.
+use super::*;
+use crate::{
+    ghost::Ghost,
+    level::LEVELS,
+    map::TILE_SIZE,
+    replay::{Replay, ReplayError},
+};
+
+/// An 8x5 room walled in on every side, with another wall in column 5. The cat starts
+/// in the middle of tile (2, 2).
+const ROOM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="8" height="5" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" source="spritesheet.tsx"/>
+ <layer id="1" name="floor" width="8" height="5">
+  <data encoding="csv">
+68,68,68,68,68,68,68,68,
+68,49,49,49,49,68,49,68,
+68,49,49,49,49,68,49,68,
+68,49,49,49,49,68,49,68,
+68,68,68,68,68,68,68,68
+</data>
+ </layer>
+ <objectgroup id="2" name="objects">
+  <object id="1" name="player_start" x="40" y="40">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+/// A 10x5 hall with a mouse hole in the top right corner, tile (8, 1). The cat starts
+/// on tile (1, 2).
+const HALL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="10" height="5" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" source="spritesheet.tsx"/>
+ <layer id="1" name="floor" width="10" height="5">
+  <data encoding="csv">
+68,68,68,68,68,68,68,68,68,68,
+68,49,49,49,49,49,49,49,49,68,
+68,49,49,49,49,49,49,49,49,68,
+68,49,49,49,49,49,49,49,49,68,
+68,68,68,68,68,68,68,68,68,68
+</data>
+ </layer>
+ <objectgroup id="2" name="objects">
+  <object id="1" name="player_start" x="24" y="40">
+   <point/>
+  </object>
+  <object id="2" name="hole" type="mouse_hole" x="136" y="24">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+/// A level on `ROOM` that never spawns mice, so nothing random happens.
+static ROOM_LEVEL: Level = Level {
+    id: "test_room",
+    name: "Test room",
+    tilemap: ROOM,
+    time_limit: 30.0,
+    waves: &[],
+    max_mice: 0,
+    ramp: 0.0,
+    spawn_distance: 0.0,
+    spawn_off_screen: false,
+};
+
+/// `ROOM` with a wave of mice every two seconds.
+static BUSY_ROOM_LEVEL: Level = Level {
+    id: "test_busy_room",
+    waves: &[Wave::of(0.0, 5), Wave::of(2.0, 5)],
+    max_mice: 100,
+    ..ROOM_LEVEL
+};
+
+/// `HALL` without waves, so the only mice are the ones a test adds.
+static HALL_LEVEL: Level = Level {
+    id: "test_hall",
+    tilemap: HALL,
+    ..ROOM_LEVEL
+};
+
+fn run(world: &mut World, seconds: f32, input: Input) {
+    for _ in 0..(seconds * TICK_RATE).round() as u32 {
+        world.update(TICK, input);
+    }
+}
+fn held(x: f32, y: f32) -> Input {
+    Input {
+        direction: vec2(x, y),
+    }
+}
+fn species(name: &str) -> &'static Species {
+    SPECIES.iter().find(|f| f.name == name).unwrap()
+}
+fn mouse_at(pos: Vec2, species_name: &str) -> Mouse {
+    let species = species(species_name);
+    Mouse {
+        species,
+        behaviour: species.behaviour,
+        scare_timer: 0.0,
+        random_direction_cooldown: 0.0,
+        travelled: 0.0,
+        pos,
+        prev_pos: pos,
+        speed: species.speed,
+        size: species.size(),
+        direction: Vec2::ZERO,
+    }
+}
+
+#[test]
+fn cat_starts_on_player_start() {
+    let world = World::new(&ROOM_LEVEL, 0).unwrap();
+    let centre = world.cat.pos + world.cat.size / 2.0;
+    assert_eq!(centre, Vec2::splat(2.5 * TILE_SIZE));
+}
+
+#[test]
+fn cat_is_stopped_by_collision_tiles() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    run(&mut world, 3.0, held(1.0, 0.0));
+    // The hitbox is shrunk by 4 on each side, so it touches the wall in column 5 before
+    // the sprite does.
+    let right = world.cat.pos.x + world.cat.size.x - 4.0;
+    assert!(
+        (right - 5.0 * TILE_SIZE).abs() < 0.01,
+        "right edge at {right}"
+    );
+
+    run(&mut world, 3.0, held(0.0, -1.0));
+    let top = world.cat.pos.y + 4.0;
+    assert!((top - TILE_SIZE).abs() < 0.01, "top edge at {top}");
+}
+
+#[test]
+fn cat_cannot_tunnel_through_walls_diagonally() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    run(&mut world, 10.0, held(1.0, 1.0));
+    let tile = Map::world_to_tile(world.cat.pos + world.cat.size / 2.0);
+    assert!(tile.0 < 5 && tile.1 < 4, "cat ended up on {tile:?}");
+}
+
+#[test]
+fn eating_a_mouse_adds_one_kill() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    world
+        .mice
+        .push(mouse_at(world.cat.pos + vec2(0.0, 1.0), "mouse"));
+    world.update(TICK, Input::default());
+    assert!(world.mice.is_empty());
+    assert_eq!(world.kills, 1);
+}
+
+#[test]
+fn eating_a_rainbow_mouse_adds_three_kills() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    world
+        .mice
+        .push(mouse_at(world.cat.pos + vec2(0.0, 1.0), "rainbow"));
+    world.update(TICK, Input::default());
+    assert!(world.mice.is_empty());
+    assert_eq!(world.kills, 3);
+}
+
+#[test]
+fn mice_out_of_reach_are_not_eaten() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    world.mice.push(mouse_at(Map::tile_to_world(6, 2), "mouse"));
+    world.update(TICK, Input::default());
+    assert_eq!(world.mice.len(), 1);
+    assert_eq!(world.kills, 0);
+}
+
+#[test]
+fn timer_ends_the_round_after_30_seconds() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    run(&mut world, 29.9, Input::default());
+    assert!(!world.is_over());
+    run(&mut world, 0.2, Input::default());
+    assert!(world.is_over());
+}
+
+#[test]
+fn same_seed_and_input_play_out_the_same() {
+    let play = |seed| {
+        let mut world = World::new(&BUSY_ROOM_LEVEL, seed).unwrap();
+        run(&mut world, 5.0, held(1.0, 1.0));
+        run(&mut world, 5.0, held(-1.0, 0.0));
+        let mice: Vec<Vec2> = world.mice.iter().map(|f| f.pos).collect();
+        (world.cat.pos, mice, world.kills)
+    };
+    let first = play(42);
+    assert!(!first.1.is_empty());
+    assert_eq!(first, play(42));
+}
+
+#[test]
+fn replay_survives_storage() {
+    let mut replay = Replay::new(BUSY_ROOM_LEVEL.id, 7);
+    for input in [
+        held(1.0, 0.0),
+        held(1.0, 0.0),
+        held(0.0, -1.0),
+        held(0.0, 0.0),
+    ] {
+        replay.record(input);
+    }
+    assert_eq!(replay.to_string().parse::<Replay>(), Ok(replay.clone()));
+    assert_eq!(replay.inputs().count(), 4);
+    assert_eq!("not a replay".parse::<Replay>(), Err(ReplayError::Invalid));
+    let other_rules = replay
+        .to_string()
+        .replace(&format!("rules {RULES_VERSION}"), "rules 0");
+    assert_eq!(other_rules.parse::<Replay>(), Err(ReplayError::Rules(0)));
+}
+
+#[test]
+fn replays_must_cover_exactly_their_round() {
+    static SHORT_ROOM_LEVEL: Level = Level {
+        id: "test_short_room",
+        time_limit: 1.0,
+        ..BUSY_ROOM_LEVEL
+    };
+    let record = |ticks| {
+        let mut replay = Replay::new(SHORT_ROOM_LEVEL.id, 8);
+        for _ in 0..ticks {
+            replay.record(held(1.0, 0.0));
+        }
+        replay
+    };
+    let mut world = World::new(&SHORT_ROOM_LEVEL, 8).unwrap();
+    let mut ticks = 0;
+    while !world.is_over() {
+        world.update(TICK, held(1.0, 0.0));
+        ticks += 1;
+    }
+    assert_eq!(record(ticks).check(&SHORT_ROOM_LEVEL), Ok(()));
+    assert_eq!(
+        record(ticks - 1).check(&SHORT_ROOM_LEVEL),
+        Err(ReplayError::Length)
+    );
+    assert_eq!(
+        record(ticks + 1).check(&SHORT_ROOM_LEVEL),
+        Err(ReplayError::Length)
+    );
+    assert_eq!(
+        record(ticks).check(&BUSY_ROOM_LEVEL),
+        Err(ReplayError::Level(SHORT_ROOM_LEVEL.id.to_string()))
+    );
+}
+
+#[test]
+fn replay_plays_out_like_the_recorded_run() {
+    let mut replay = Replay::new(BUSY_ROOM_LEVEL.id, 1234);
+    let mut live = World::new(&BUSY_ROOM_LEVEL, replay.seed).unwrap();
+    for tick in 0..600 {
+        let angle = tick as f32 / 40.0;
+        let input = replay.record(held(angle.cos().round(), angle.sin().round()));
+        live.update(TICK, input);
+    }
+
+    let replay: Replay = replay.to_string().parse().unwrap();
+    let mut watched = World::new(&BUSY_ROOM_LEVEL, replay.seed).unwrap();
+    for input in replay.inputs() {
+        watched.update(TICK, input);
+    }
+    assert_eq!(watched.cat.pos, live.cat.pos);
+    assert_eq!(watched.kills, live.kills);
+    let positions = |world: &World| world.mice.iter().map(|f| f.pos).collect::<Vec<_>>();
+    assert_eq!(positions(&watched), positions(&live));
+}
+
+#[test]
+fn ghost_follows_the_recorded_path() {
+    let mut world = World::new(&BUSY_ROOM_LEVEL, 5).unwrap();
+    let mut ghost = Ghost::default();
+    ghost.record(&world);
+    let mut path = vec![(world.cat.pos, world.kills)];
+    for _ in 0..120 {
+        world.update(TICK, held(1.0, 1.0));
+        ghost.record(&world);
+        path.push((world.cat.pos, world.kills));
+    }
+    let ghost: Ghost = ghost.to_string().parse().unwrap();
+    for tick in [0, 6, 60, 120] {
+        let (pos, _, kills) = ghost.at(tick as f32).unwrap();
+        assert_eq!((pos, kills), path[tick]);
+    }
+    let halfway = ghost.at(3.0).unwrap().0;
+    assert_eq!(halfway, path[0].0.lerp(path[6].0, 0.5));
+    assert!(ghost.at(130.0).is_none());
+}
+
+#[test]
+fn half_pushed_stick_moves_the_cat_at_half_speed() {
+    let mut full = World::new(&ROOM_LEVEL, 0).unwrap();
+    let mut half = World::new(&ROOM_LEVEL, 0).unwrap();
+    let start = full.cat.pos;
+    run(&mut full, 0.25, held(0.0, 1.0));
+    run(&mut half, 0.25, held(0.0, 0.5));
+    let ratio = (half.cat.pos.y - start.y) / (full.cat.pos.y - start.y);
+    assert!((ratio - 0.5).abs() < 0.01, "moved {ratio} as far");
+    assert!((half.cat.speed - 0.5).abs() < 0.01);
+}
+
+#[test]
+fn scared_mice_escape_down_the_nearest_mouse_hole() {
+    let mut world = World::new(&HALL_LEVEL, 0).unwrap();
+    let mut mouse = mouse_at(Map::tile_to_world(3, 2) + Vec2::splat(10.0), "mouse");
+    // Scared from anywhere, so it never stops to wander.
+    mouse.behaviour.scare_radius = 10_000.0;
+    world.mice.push(mouse);
+    run(&mut world, 3.0, Input::default());
+    assert!(world.mice.is_empty());
+    assert_eq!(world.escapes, 1);
+    assert_eq!(world.kills, 0);
+}
+
+#[test]
+fn calm_mice_do_not_escape_down_holes() {
+    let mut world = World::new(&HALL_LEVEL, 0).unwrap();
+    let mut mouse = mouse_at(Map::tile_to_world(8, 1) + Vec2::splat(10.0), "mouse");
+    mouse.random_direction_cooldown = 100.0;
+    world.mice.push(mouse);
+    run(&mut world, 0.5, Input::default());
+    assert_eq!(world.mice.len(), 1);
+    assert_eq!(world.escapes, 0);
+}
+
+#[test]
+fn mice_that_appear_on_a_hole_do_not_escape_at_once() {
+    let mut world = World::new(&HALL_LEVEL, 0).unwrap();
+    // Two tiles from the hole, well inside the mouse's scare radius.
+    world.cat.pos = Map::tile_to_world(6, 2);
+    world.mice.push(mouse_at(
+        Map::tile_to_world(8, 1) + Vec2::splat(10.0),
+        "mouse",
+    ));
+    run(&mut world, 0.1, Input::default());
+    assert_eq!(world.mice.len(), 1);
+    assert!(world.mice[0].scare_timer > 0.0);
+    assert_eq!(world.escapes, 0);
+}
+
+#[test]
+fn mouse_holes_are_not_spawn_regions() {
+    let world = World::new(&HALL_LEVEL, 0).unwrap();
+    assert_eq!(world.map.mouse_holes.len(), 1);
+    assert!(world.map.spawn_regions.is_empty());
+}
+
+#[test]
+fn calm_mice_leave_through_exits() {
+    let mut world = World::new(&HALL_LEVEL, 0).unwrap();
+    let exit = Map::tile_to_world(7, 3);
+    world
+        .map
+        .exit_zones
+        .push(Rect::new(exit.x, exit.y, TILE_SIZE, TILE_SIZE));
+    let mut mouse = mouse_at(exit + Vec2::splat(10.0), "mouse");
+    mouse.random_direction_cooldown = 100.0;
+    mouse.travelled = ESCAPE_AFTER;
+    world.mice.push(mouse);
+    world.update(TICK, Input::default());
+    assert!(world.mice.is_empty());
+    assert_eq!(world.escapes, 1);
+}
+
+#[test]
+fn mice_keep_apart_from_each_other() {
+    let mut world = World::new(&ROOM_LEVEL, 0).unwrap();
+    // Column 6 is walled off from the cat, so nothing scares them, and they are not about
+    // to pick a direction of their own.
+    let start = Map::tile_to_world(6, 2);
+    for offset in [0.0, 4.0] {
+        let mut mouse = mouse_at(start + vec2(0.0, offset), "mouse");
+        mouse.random_direction_cooldown = 100.0;
+        world.mice.push(mouse);
+    }
+    run(&mut world, 0.5, Input::default());
+    let gap = world.mice[0].pos.distance(world.mice[1].pos);
+    assert!(gap > 20.0, "mice are {gap} apart");
+}
+
+#[test]
+fn every_species_loads() {
+    assert_eq!(species("mouse").score, 1);
+    assert_eq!(species("rainbow").score, 3);
+    for species in SPECIES.iter() {
+        assert!(species.size().x > 0.0, "{} has no size", species.name);
+    }
+    let json = include_str!("../../assets/mice.json").replace("\"mouse\"", "\"rat\"");
+    assert!(Species::parse(&json).is_err());
+}
+
+#[test]
+fn waves_stop_at_the_live_mouse_cap() {
+    static CROWDED_ROOM_LEVEL: Level = Level {
+        id: "test_crowded_room",
+        waves: &[Wave::of(0.0, 10), Wave::of(0.5, 10)],
+        max_mice: 14,
+        ..ROOM_LEVEL
+    };
+    let mut world = World::new(&CROWDED_ROOM_LEVEL, 3).unwrap();
+    world.update(TICK, Input::default());
+    assert_eq!(world.mice.len(), 10);
+    run(&mut world, 5.0, Input::default());
+    assert!(world.mice.len() <= 14, "{} mice", world.mice.len());
+}
+
+#[test]
+fn small_rooms_fill_up_without_hanging() {
+    // ROOM only has 12 floor tiles on the cat's side of the wall.
+    static PACKED_ROOM_LEVEL: Level = Level {
+        id: "test_packed_room",
+        waves: &[Wave::of(0.0, 1000)],
+        max_mice: 1000,
+        ..ROOM_LEVEL
+    };
+    let mut world = World::new(&PACKED_ROOM_LEVEL, 9).unwrap();
+    world.update(TICK, Input::default());
+    assert_eq!(world.mice.len(), 12);
+}
+
+#[test]
+fn mice_only_spawn_where_the_cat_can_reach_and_not_too_close() {
+    static SAFE_ROOM_LEVEL: Level = Level {
+        id: "test_safe_room",
+        waves: &[Wave::of(0.0, 1000)],
+        max_mice: 1000,
+        spawn_distance: 100.0,
+        ..ROOM_LEVEL
+    };
+    let mut world = World::new(&SAFE_ROOM_LEVEL, 4).unwrap();
+    world.update(TICK, Input::default());
+    let cat = world.cat.pos + world.cat.size / 2.0;
+    assert!(!world.mice.is_empty());
+    for mouse in world.mice.iter() {
+        let (x, y) = Map::world_to_tile(mouse.pos + mouse.size / 2.0);
+        assert!(x < 5, "mouse spawned behind the wall on ({x}, {y})");
+        let centre = Map::tile_to_world(x, y) + Vec2::splat(TILE_SIZE / 2.0);
+        assert!(centre.distance(cat) >= 100.0, "mouse spawned on ({x}, {y})");
+    }
+}
+
+#[test]
+fn off_screen_spawns_stay_out_of_view() {
+    static HIDDEN_HALL_LEVEL: Level = Level {
+        id: "test_hidden_hall",
+        waves: &[Wave::of(0.0, 1000)],
+        max_mice: 1000,
+        spawn_off_screen: true,
+        ..HALL_LEVEL
+    };
+    let mut world = World::new(&HIDDEN_HALL_LEVEL, 4).unwrap();
+    world.update(TICK, Input::default());
+    assert!(!world.mice.is_empty());
+    for mouse in world.mice.iter() {
+        let rect = Rect::new(mouse.pos.x, mouse.pos.y, mouse.size.x, mouse.size.y);
+        assert!(!rect.overlaps(&world.view()));
+    }
+}
+
+#[test]
+fn wave_scripts_name_real_species_and_regions() {
+    for level in LEVELS.iter() {
+        let map = Map::new(level.tilemap).unwrap();
+        for wave in level.waves {
+            for (name, _) in wave.species {
+                assert!(
+                    SPECIES.iter().any(|f| f.name == *name),
+                    "{name} in {}",
+                    level.id
+                );
+            }
+            for name in wave.regions {
+                assert!(
+                    map.spawn_regions.iter().any(|f| f.name == *name),
+                    "{name} in {}",
+                    level.id
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! The kinds of mice, read from `assets/mice.json` so a new one needs no code.
+use crate::sim::{Behaviour, sprite_size};
+use macroquad::math::Vec2;
+use nanoserde::{DeJson, DeJsonErr};
+use std::{fmt, sync::LazyLock};
+
+/// Sprites a species can use, by name.
+pub const SPRITES: &[(&str, &[u8])] = &[("mouse", include_bytes!("../assets/mouse.ase"))];
+
+pub static SPECIES: LazyLock<Vec<Species>> = LazyLock::new(|| {
+    Species::parse(include_str!("../assets/mice.json"))
+        .unwrap_or_else(|err| panic!("could not load assets/mice.json: {err}"))
+});
+
+/// How a mouse is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default, DeJson)]
+pub enum Material {
+    #[default]
+    #[nserde(rename = "plain")]
+    Plain,
+    #[nserde(rename = "rainbow")]
+    Rainbow,
+}
+#[derive(Debug, Clone, DeJson)]
+pub struct Species {
+    pub name: String,
+    /// One of `SPRITES`.
+    pub sprite: String,
+    /// Size of the sprite and hitbox, relative to the sprite file.
+    pub scale: f32,
+    pub tint: [u8; 3],
+    pub material: Material,
+    pub speed: f32,
+    /// Kills added for eating one.
+    pub score: u32,
+    /// Chance of spawning relative to the other species.
+    pub spawn_weight: f32,
+    pub behaviour: Behaviour,
+    /// Sprite size times `scale`, filled in by `parse`.
+    #[nserde(skip)]
+    size: Option<Vec2>,
+}
+impl Species {
+    /// Reads a list of species from JSON.
+    pub fn parse(json: &str) -> Result<Vec<Self>, ParseSpeciesError> {
+        let mut species = Vec::<Self>::deserialize_json(json).map_err(ParseSpeciesError::Json)?;
+        for species in species.iter_mut() {
+            let (_, bytes) = SPRITES
+                .iter()
+                .find(|f| f.0 == species.sprite)
+                .ok_or_else(|| {
+                    ParseSpeciesError::UnknownSprite(species.name.clone(), species.sprite.clone())
+                })?;
+            species.size = Some(sprite_size(bytes) * species.scale);
+        }
+        Ok(species)
+    }
+    /// Size of the sprite and hitbox in world space.
+    pub fn size(&self) -> Vec2 {
+        self.size.unwrap_or_default()
+    }
+}
+#[derive(Debug)]
+pub enum ParseSpeciesError {
+    Json(DeJsonErr),
+    /// A species and the sprite it asked for.
+    UnknownSprite(String, String),
+}
+impl fmt::Display for ParseSpeciesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "{err}"),
+            Self::UnknownSprite(species, sprite) => {
+                write!(f, "{species} uses sprite {sprite}, which doesn't exist")
+            }
+        }
+    }
+}
+impl std::error::Error for ParseSpeciesError {}
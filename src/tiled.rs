@@ -0,0 +1,648 @@
+//! Loader for Tiled TMX maps and TSX tilesets.
+use base64::Engine;
+use roxmltree::{Document, Node};
+use std::{collections::HashMap, fmt, io::Read, str::FromStr};
+
+#[derive(Debug)]
+pub enum TiledError {
+    Xml(roxmltree::Error),
+    UnexpectedRoot {
+        expected: &'static str,
+        found: String,
+    },
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+    InvalidAttribute {
+        element: String,
+        attribute: &'static str,
+        value: String,
+    },
+    UnsupportedOrientation(String),
+    UnsupportedEncoding(String),
+    UnsupportedCompression(String),
+    Base64(base64::DecodeError),
+    Decompress(std::io::Error),
+    InvalidTileData(String),
+    DataLength {
+        layer: String,
+        expected: usize,
+        found: usize,
+    },
+    MissingTileset(String),
+    UnknownGid(u32),
+}
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Xml(err) => write!(f, "invalid xml: {err}"),
+            Self::UnexpectedRoot { expected, found } => {
+                write!(f, "expected a <{expected}> document, found <{found}>")
+            }
+            Self::MissingAttribute { element, attribute } => {
+                write!(f, "<{element}> is missing the `{attribute}` attribute")
+            }
+            Self::InvalidAttribute {
+                element,
+                attribute,
+                value,
+            } => write!(f, "<{element}> has an invalid `{attribute}`: {value:?}"),
+            Self::UnsupportedOrientation(orientation) => {
+                write!(f, "unsupported map orientation {orientation:?}")
+            }
+            Self::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported layer encoding {encoding:?}")
+            }
+            Self::UnsupportedCompression(compression) => {
+                write!(f, "unsupported layer compression {compression:?}")
+            }
+            Self::Base64(err) => write!(f, "invalid base64 layer data: {err}"),
+            Self::Decompress(err) => write!(f, "could not decompress layer data: {err}"),
+            Self::InvalidTileData(value) => write!(f, "invalid tile id {value:?}"),
+            Self::DataLength {
+                layer,
+                expected,
+                found,
+            } => write!(
+                f,
+                "layer {layer:?} should have {expected} tiles but has {found}"
+            ),
+            Self::MissingTileset(source) => write!(f, "tileset {source:?} could not be found"),
+            Self::UnknownGid(gid) => write!(f, "tile id {gid} does not belong to any tileset"),
+        }
+    }
+}
+impl std::error::Error for TiledError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Xml(err) => Some(err),
+            Self::Base64(err) => Some(err),
+            Self::Decompress(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+impl From<roxmltree::Error> for TiledError {
+    fn from(err: roxmltree::Error) -> Self {
+        Self::Xml(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flip {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub diagonal: bool,
+}
+
+/// A global tile id as stored in layer data, including the flip flags in its top bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Gid(pub u32);
+impl Gid {
+    const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+    const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+    const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+    const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+    const FLAGS: u32 = Self::FLIPPED_HORIZONTALLY
+        | Self::FLIPPED_VERTICALLY
+        | Self::FLIPPED_DIAGONALLY
+        | Self::ROTATED_HEXAGONAL_120;
+    pub const EMPTY: Gid = Gid(0);
+
+    pub fn id(self) -> u32 {
+        self.0 & !Self::FLAGS
+    }
+    pub fn is_empty(self) -> bool {
+        self.id() == 0
+    }
+    pub fn flip(self) -> Flip {
+        Flip {
+            horizontal: self.0 & Self::FLIPPED_HORIZONTALLY != 0,
+            vertical: self.0 & Self::FLIPPED_VERTICALLY != 0,
+            diagonal: self.0 & Self::FLIPPED_DIAGONALLY != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Strings, colors, file paths and class names are kept as written.
+    String(String),
+}
+
+/// Custom properties attached to a map, layer, tile or object.
+#[derive(Debug, Clone, Default)]
+pub struct Properties(HashMap<String, PropertyValue>);
+impl Properties {
+    fn from_parent(node: Node) -> Result<Self, TiledError> {
+        let mut properties = HashMap::new();
+        let Some(list) = node.children().find(|f| f.has_tag_name("properties")) else {
+            return Ok(Self(properties));
+        };
+        for property in list.children().filter(|f| f.has_tag_name("property")) {
+            let name: String = required(property, "name")?;
+            let value = match property.attribute("type").unwrap_or("string") {
+                "bool" => PropertyValue::Bool(required(property, "value")?),
+                "int" | "object" => PropertyValue::Int(optional(property, "value")?.unwrap_or(0)),
+                "float" => PropertyValue::Float(optional(property, "value")?.unwrap_or(0.0)),
+                _ => PropertyValue::String(
+                    property
+                        .attribute("value")
+                        .or_else(|| property.text())
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+            };
+            properties.insert(name, value);
+        }
+        Ok(Self(properties))
+    }
+    pub fn get(&self, name: &str) -> Option<&PropertyValue> {
+        self.0.get(name)
+    }
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            PropertyValue::Int(value) => Some(*value as f64),
+            PropertyValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            PropertyValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            PropertyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+    /// Copies every property from `other`, replacing ones with the same name.
+    pub fn merge(&mut self, other: &Properties) {
+        self.0
+            .extend(other.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub tile_id: u32,
+    /// In milliseconds.
+    pub duration: u32,
+}
+
+/// Per-tile data from a tileset's `<tile>` elements.
+#[derive(Debug, Clone, Default)]
+pub struct TilesetTile {
+    pub properties: Properties,
+    /// Empty unless the tile is animated.
+    pub animation: Vec<Frame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub columns: u32,
+    /// Keyed by local tile id. Tiles without any extra data are absent.
+    pub tiles: HashMap<u32, TilesetTile>,
+}
+impl Tileset {
+    pub fn parse(tsx: &str) -> Result<Self, TiledError> {
+        let document = Document::parse(tsx)?;
+        let root = document.root_element();
+        expect_root(root, "tileset")?;
+        Self::from_node(root)
+    }
+    fn from_node(node: Node) -> Result<Self, TiledError> {
+        let mut tiles = HashMap::new();
+        for tile in node.children().filter(|f| f.has_tag_name("tile")) {
+            let mut animation = Vec::new();
+            if let Some(frames) = tile.children().find(|f| f.has_tag_name("animation")) {
+                for frame in frames.children().filter(|f| f.has_tag_name("frame")) {
+                    animation.push(Frame {
+                        tile_id: required(frame, "tileid")?,
+                        duration: required(frame, "duration")?,
+                    });
+                }
+            }
+            tiles.insert(
+                required(tile, "id")?,
+                TilesetTile {
+                    properties: Properties::from_parent(tile)?,
+                    animation,
+                },
+            );
+        }
+        Ok(Self {
+            columns: required(node, "columns")?,
+            tiles,
+        })
+    }
+    /// Column and row of a local tile id within the tileset image.
+    pub fn texture_coord(&self, local_id: u32) -> (u32, u32) {
+        let columns = self.columns.max(1);
+        (local_id % columns, local_id / columns)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TilesetRef {
+    pub first_gid: u32,
+    pub tileset: Tileset,
+}
+
+/// A tile layer stored as a dense grid. For infinite maps the grid covers the
+/// bounding box of all chunks and `x`/`y` hold its offset in tiles.
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    pub name: String,
+    pub visible: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<Gid>,
+    pub properties: Properties,
+}
+impl TileLayer {
+    pub fn get(&self, x: i32, y: i32) -> Gid {
+        let (x, y) = (x - self.x, y - self.y);
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return Gid::EMPTY;
+        }
+        self.data[(y as u32 * self.width + x as u32) as usize]
+    }
+    /// Bounds of the non-empty tiles as `(min_x, min_y, max_x, max_y)`, inclusive.
+    pub fn used_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for (index, gid) in self.data.iter().enumerate() {
+            if gid.is_empty() {
+                continue;
+            }
+            let x = self.x + (index as u32 % self.width) as i32;
+            let y = self.y + (index as u32 / self.width) as i32;
+            bounds = Some(match bounds {
+                Some(b) => (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+        bounds
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rect,
+    Ellipse,
+    Point,
+    Polygon(Vec<(f32, f32)>),
+    Polyline(Vec<(f32, f32)>),
+}
+
+/// An object placed in an object layer. Positions are in map pixels, with
+/// polygon points relative to `x`/`y`.
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub name: String,
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+impl Object {
+    fn from_node(node: Node) -> Result<Self, TiledError> {
+        let points = |tag: &str| -> Result<Option<Vec<(f32, f32)>>, TiledError> {
+            let Some(child) = node.children().find(|f| f.has_tag_name(tag)) else {
+                return Ok(None);
+            };
+            let points: String = required(child, "points")?;
+            points
+                .split_whitespace()
+                .map(|point| {
+                    point
+                        .split_once(',')
+                        .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+                        .ok_or_else(|| TiledError::InvalidAttribute {
+                            element: tag.to_string(),
+                            attribute: "points",
+                            value: points.clone(),
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Some)
+        };
+        let shape = if node.children().any(|f| f.has_tag_name("point")) {
+            ObjectShape::Point
+        } else if node.children().any(|f| f.has_tag_name("ellipse")) {
+            ObjectShape::Ellipse
+        } else if let Some(points) = points("polygon")? {
+            ObjectShape::Polygon(points)
+        } else if let Some(points) = points("polyline")? {
+            ObjectShape::Polyline(points)
+        } else {
+            ObjectShape::Rect
+        };
+        let width = optional(node, "width")?.unwrap_or(0.0);
+        let height = optional(node, "height")?.unwrap_or(0.0);
+        let mut y = required(node, "y")?;
+        // Tile objects are anchored at their bottom left corner.
+        if node.attribute("gid").is_some() {
+            y -= height;
+        }
+        Ok(Self {
+            name: node.attribute("name").unwrap_or_default().to_string(),
+            class: node
+                .attribute("type")
+                .or_else(|| node.attribute("class"))
+                .unwrap_or_default()
+                .to_string(),
+            x: required(node, "x")?,
+            y,
+            width,
+            height,
+            shape,
+            properties: Properties::from_parent(node)?,
+        })
+    }
+    /// The object's class, falling back to its name when no class is set.
+    pub fn kind(&self) -> &str {
+        if self.class.is_empty() {
+            &self.name
+        } else {
+            &self.class
+        }
+    }
+    /// Bounding box as `(x, y, width, height)` in map pixels. Points have no size.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match &self.shape {
+            ObjectShape::Point => (self.x, self.y, 0.0, 0.0),
+            ObjectShape::Rect | ObjectShape::Ellipse => (self.x, self.y, self.width, self.height),
+            ObjectShape::Polygon(points) | ObjectShape::Polyline(points) => {
+                let min_x = points.iter().map(|f| f.0).fold(f32::INFINITY, f32::min);
+                let min_y = points.iter().map(|f| f.1).fold(f32::INFINITY, f32::min);
+                let max_x = points.iter().map(|f| f.0).fold(f32::NEG_INFINITY, f32::max);
+                let max_y = points.iter().map(|f| f.1).fold(f32::NEG_INFINITY, f32::max);
+                if points.is_empty() {
+                    (self.x, self.y, 0.0, 0.0)
+                } else {
+                    (self.x + min_x, self.y + min_y, max_x - min_x, max_y - min_y)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub infinite: bool,
+    pub tilesets: Vec<TilesetRef>,
+    pub layers: Vec<TileLayer>,
+    /// Objects from every object layer, in document order.
+    pub objects: Vec<Object>,
+}
+impl TileMap {
+    /// Parses a TMX document. External tilesets are looked up by their `source`
+    /// attribute through `load_tileset`.
+    pub fn parse<'s>(
+        tmx: &str,
+        load_tileset: impl Fn(&str) -> Option<&'s str>,
+    ) -> Result<Self, TiledError> {
+        let document = Document::parse(tmx)?;
+        let root = document.root_element();
+        expect_root(root, "map")?;
+        if let Some(orientation) = root.attribute("orientation")
+            && orientation != "orthogonal"
+        {
+            return Err(TiledError::UnsupportedOrientation(orientation.to_string()));
+        }
+        let mut map = Self {
+            width: required(root, "width")?,
+            height: required(root, "height")?,
+            infinite: optional::<u8>(root, "infinite")?.unwrap_or(0) == 1,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            objects: Vec::new(),
+        };
+        for tileset in root.children().filter(|f| f.has_tag_name("tileset")) {
+            let first_gid = required(tileset, "firstgid")?;
+            let tileset = if let Some(source) = tileset.attribute("source") {
+                let tsx = load_tileset(source)
+                    .ok_or_else(|| TiledError::MissingTileset(source.to_string()))?;
+                Tileset::parse(tsx)?
+            } else {
+                Tileset::from_node(tileset)?
+            };
+            map.tilesets.push(TilesetRef { first_gid, tileset });
+        }
+        map.tilesets.sort_by_key(|f| f.first_gid);
+        map.read_layers(root)?;
+        Ok(map)
+    }
+    fn read_layers(&mut self, parent: Node) -> Result<(), TiledError> {
+        for node in parent.children().filter(|f| f.is_element()) {
+            match node.tag_name().name() {
+                "layer" => {
+                    let layer = self.read_tile_layer(node)?;
+                    self.layers.push(layer);
+                }
+                "objectgroup" => {
+                    for object in node.children().filter(|f| f.has_tag_name("object")) {
+                        self.objects.push(Object::from_node(object)?);
+                    }
+                }
+                "group" => self.read_layers(node)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    fn read_tile_layer(&self, node: Node) -> Result<TileLayer, TiledError> {
+        let name = node.attribute("name").unwrap_or_default().to_string();
+        let mut layer = TileLayer {
+            visible: optional::<u8>(node, "visible")?.unwrap_or(1) == 1,
+            x: 0,
+            y: 0,
+            width: optional(node, "width")?.unwrap_or(self.width),
+            height: optional(node, "height")?.unwrap_or(self.height),
+            data: Vec::new(),
+            properties: Properties::from_parent(node)?,
+            name,
+        };
+        let Some(data) = node.children().find(|f| f.has_tag_name("data")) else {
+            layer.data = vec![Gid::EMPTY; (layer.width * layer.height) as usize];
+            return Ok(layer);
+        };
+        let encoding = data.attribute("encoding");
+        let compression = data.attribute("compression");
+        let chunks: Vec<Node> = data
+            .children()
+            .filter(|f| f.has_tag_name("chunk"))
+            .collect();
+        if !self.infinite {
+            let tiles = decode_data(data, encoding, compression)?;
+            check_length(&layer.name, (layer.width * layer.height) as usize, &tiles)?;
+            layer.data = tiles;
+            return Ok(layer);
+        }
+
+        let mut decoded = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let x: i32 = required(chunk, "x")?;
+            let y: i32 = required(chunk, "y")?;
+            let width: u32 = required(chunk, "width")?;
+            let height: u32 = required(chunk, "height")?;
+            let tiles = decode_data(chunk, encoding, compression)?;
+            check_length(&layer.name, (width * height) as usize, &tiles)?;
+            decoded.push((x, y, width, height, tiles));
+        }
+        let min_x = decoded.iter().map(|f| f.0).min().unwrap_or(0);
+        let min_y = decoded.iter().map(|f| f.1).min().unwrap_or(0);
+        let max_x = decoded.iter().map(|f| f.0 + f.2 as i32).max().unwrap_or(0);
+        let max_y = decoded.iter().map(|f| f.1 + f.3 as i32).max().unwrap_or(0);
+        layer.x = min_x;
+        layer.y = min_y;
+        layer.width = (max_x - min_x) as u32;
+        layer.height = (max_y - min_y) as u32;
+        layer.data = vec![Gid::EMPTY; (layer.width * layer.height) as usize];
+        for (x, y, width, _, tiles) in decoded {
+            for (index, gid) in tiles.into_iter().enumerate() {
+                let tx = (x - min_x) as u32 + index as u32 % width;
+                let ty = (y - min_y) as u32 + index as u32 / width;
+                layer.data[(ty * layer.width + tx) as usize] = gid;
+            }
+        }
+        Ok(layer)
+    }
+    /// Finds the tileset a gid belongs to, returning it with the tile's local id.
+    pub fn tileset_for(&self, gid: Gid) -> Result<(&TilesetRef, u32), TiledError> {
+        let id = gid.id();
+        self.tilesets
+            .iter()
+            .rev()
+            .find(|f| f.first_gid <= id)
+            .map(|f| (f, id - f.first_gid))
+            .ok_or(TiledError::UnknownGid(id))
+    }
+    /// Bounds of the non-empty tiles across all layers as `(min_x, min_y, max_x, max_y)`, inclusive.
+    pub fn used_bounds(&self) -> Option<(i32, i32, i32, i32)> {
+        self.layers
+            .iter()
+            .filter_map(|f| f.used_bounds())
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    }
+}
+
+fn expect_root(node: Node, expected: &'static str) -> Result<(), TiledError> {
+    if node.has_tag_name(expected) {
+        Ok(())
+    } else {
+        Err(TiledError::UnexpectedRoot {
+            expected,
+            found: node.tag_name().name().to_string(),
+        })
+    }
+}
+fn optional<T: FromStr>(node: Node, attribute: &'static str) -> Result<Option<T>, TiledError> {
+    node.attribute(attribute)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| TiledError::InvalidAttribute {
+                    element: node.tag_name().name().to_string(),
+                    attribute,
+                    value: value.to_string(),
+                })
+        })
+        .transpose()
+}
+fn required<T: FromStr>(node: Node, attribute: &'static str) -> Result<T, TiledError> {
+    optional(node, attribute)?.ok_or_else(|| TiledError::MissingAttribute {
+        element: node.tag_name().name().to_string(),
+        attribute,
+    })
+}
+fn check_length(layer: &str, expected: usize, tiles: &[Gid]) -> Result<(), TiledError> {
+    if tiles.len() == expected {
+        Ok(())
+    } else {
+        Err(TiledError::DataLength {
+            layer: layer.to_string(),
+            expected,
+            found: tiles.len(),
+        })
+    }
+}
+fn decode_data(
+    node: Node,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+) -> Result<Vec<Gid>, TiledError> {
+    let text = || {
+        node.children()
+            .filter(|f| f.is_text())
+            .filter_map(|f| f.text())
+            .collect::<String>()
+    };
+    match encoding {
+        None => node
+            .children()
+            .filter(|f| f.has_tag_name("tile"))
+            .map(|f| Ok(Gid(optional(f, "gid")?.unwrap_or(0))))
+            .collect(),
+        Some("csv") => text()
+            .split(',')
+            .map(str::trim)
+            .filter(|f| !f.is_empty())
+            .map(|f| {
+                f.parse::<u32>()
+                    .map(Gid)
+                    .map_err(|_| TiledError::InvalidTileData(f.to_string()))
+            })
+            .collect(),
+        Some("base64") => {
+            let text: String = text().split_whitespace().collect();
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(TiledError::Base64)?;
+            let bytes = decompress(bytes, compression)?;
+            if bytes.len() % 4 != 0 {
+                return Err(TiledError::InvalidTileData(format!(
+                    "{} bytes of base64 data",
+                    bytes.len()
+                )));
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|f| Gid(u32::from_le_bytes([f[0], f[1], f[2], f[3]])))
+                .collect())
+        }
+        Some(other) => Err(TiledError::UnsupportedEncoding(other.to_string())),
+    }
+}
+fn decompress(bytes: Vec<u8>, compression: Option<&str>) -> Result<Vec<u8>, TiledError> {
+    let mut out = Vec::new();
+    let result = match compression {
+        None | Some("") => return Ok(bytes),
+        Some("zlib") => flate2::read::ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut out),
+        Some("gzip") => flate2::read::GzDecoder::new(bytes.as_slice()).read_to_end(&mut out),
+        Some("zstd") => ruzstd::decoding::StreamingDecoder::new(bytes.as_slice())
+            .map_err(std::io::Error::other)
+            .and_then(|mut f| f.read_to_end(&mut out)),
+        Some(other) => return Err(TiledError::UnsupportedCompression(other.to_string())),
+    };
+    result.map_err(TiledError::Decompress)?;
+    Ok(out)
+}
+#[cfg(test)]
+mod tests;
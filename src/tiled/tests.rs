@@ -0,0 +1,342 @@
+//! Small hand-written TMX and TSX documents run through the parser.
+use super::*;
+
+/// The 2x2 layer `1, 2, 3 (flipped horizontally), 4` in each of the encodings Tiled
+/// can write.
+const CSV: (&str, Option<&str>, &str) = ("csv", None, "1,2,\n2147483651,4");
+const BASE64: (&str, Option<&str>, &str) = ("base64", None, "AQAAAAIAAAADAACABAAAAA==");
+const ZLIB: (&str, Option<&str>, &str) = ("base64", Some("zlib"), "eJxjZGBgYAJiZgaGBhYgDQAC4ACL");
+const GZIP: (&str, Option<&str>, &str) = (
+    "base64",
+    Some("gzip"),
+    "H4sIAAAAAAACA2NkYGBgAmJmBoYGFiANAH1k4x4QAAAA",
+);
+const ZSTD: (&str, Option<&str>, &str) = (
+    "base64",
+    Some("zstd"),
+    "KLUv/SQQgQAAAQAAAAIAAAADAACABAAAANU+CCQ=",
+);
+const EXPECTED: [Gid; 4] = [Gid(1), Gid(2), Gid(0x8000_0003), Gid(4)];
+
+const TILESET: &str = r#"<tileset version="1.10" name="sheet" tilewidth="16" tileheight="16" tilecount="8" columns="4"/>"#;
+
+/// Wraps `body` in a 2x2 orthogonal map with an embedded tileset.
+fn map(body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <tileset firstgid="1" name="sheet" tilewidth="16" tileheight="16" tilecount="8" columns="4"/>
+ {body}
+</map>"#
+    )
+}
+fn layer((encoding, compression, data): (&str, Option<&str>, &str)) -> String {
+    let compression = compression
+        .map(|f| format!(r#" compression="{f}""#))
+        .unwrap_or_default();
+    map(&format!(
+        r#"<layer id="1" name="floor" width="2" height="2">
+  <data encoding="{encoding}"{compression}>
+{data}
+  </data>
+ </layer>"#
+    ))
+}
+fn parse(tmx: &str) -> Result<TileMap, TiledError> {
+    TileMap::parse(tmx, |_| None)
+}
+
+#[test]
+fn every_encoding_decodes_to_the_same_tiles() {
+    for data in [CSV, BASE64, ZLIB, GZIP, ZSTD] {
+        let map = parse(&layer(data)).unwrap();
+        assert_eq!(map.layers.len(), 1);
+        assert_eq!(map.layers[0].data, EXPECTED, "{data:?}");
+    }
+}
+
+#[test]
+fn xml_tile_elements_are_read_in_order() {
+    let map = parse(&map(r#"<layer id="1" name="floor" width="2" height="2">
+  <data><tile gid="1"/><tile gid="2"/><tile/><tile gid="4"/></data>
+ </layer>"#))
+    .unwrap();
+    assert_eq!(map.layers[0].data, [Gid(1), Gid(2), Gid::EMPTY, Gid(4)]);
+}
+
+#[test]
+fn flip_flags_are_separate_from_the_tile_id() {
+    let gid = Gid(0x8000_0003);
+    assert_eq!(gid.id(), 3);
+    assert_eq!(
+        gid.flip(),
+        Flip {
+            horizontal: true,
+            ..Default::default()
+        }
+    );
+
+    let gid = Gid(0x6000_0005);
+    assert_eq!(gid.id(), 5);
+    assert_eq!(
+        gid.flip(),
+        Flip {
+            horizontal: false,
+            vertical: true,
+            diagonal: true,
+        }
+    );
+
+    // Hexagonal rotation is masked off but has no flip of its own.
+    let gid = Gid(0x1000_0000);
+    assert!(gid.is_empty());
+    assert_eq!(gid.flip(), Flip::default());
+}
+
+#[test]
+fn infinite_maps_merge_chunks_into_one_grid() {
+    let tmx = r#"<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16" infinite="1">
+ <tileset firstgid="1" columns="4"/>
+ <layer id="1" name="floor" width="2" height="2">
+  <data encoding="csv">
+   <chunk x="-2" y="-1" width="2" height="1">1,2</chunk>
+   <chunk x="0" y="1" width="2" height="1">0,4</chunk>
+  </data>
+ </layer>
+</map>"#;
+    let map = parse(tmx).unwrap();
+    assert!(map.infinite);
+    let layer = &map.layers[0];
+    assert_eq!(
+        (layer.x, layer.y, layer.width, layer.height),
+        (-2, -1, 4, 3)
+    );
+    assert_eq!(layer.get(-2, -1), Gid(1));
+    assert_eq!(layer.get(-1, -1), Gid(2));
+    assert_eq!(layer.get(0, 1), Gid::EMPTY);
+    assert_eq!(layer.get(1, 1), Gid(4));
+    // Gaps between chunks and tiles outside the grid are empty.
+    assert_eq!(layer.get(0, 0), Gid::EMPTY);
+    assert_eq!(layer.get(5, 5), Gid::EMPTY);
+    assert_eq!(map.used_bounds(), Some((-2, -1, 1, 1)));
+}
+
+#[test]
+fn external_tilesets_are_loaded_by_source() {
+    let tmx = r#"<map version="1.10" orientation="orthogonal" width="1" height="1" tilewidth="16" tileheight="16">
+ <tileset firstgid="9" source="sheet.tsx"/>
+ <tileset firstgid="1" columns="2"/>
+</map>"#;
+    let map = TileMap::parse(tmx, |source| (source == "sheet.tsx").then_some(TILESET)).unwrap();
+    // Tilesets are sorted by first gid whatever order they're written in.
+    assert_eq!(map.tilesets[0].first_gid, 1);
+    assert_eq!(map.tilesets[1].tileset.columns, 4);
+
+    let (tileset, local) = map.tileset_for(Gid(0x8000_000E)).unwrap();
+    assert_eq!((tileset.first_gid, local), (9, 5));
+    assert_eq!(tileset.tileset.texture_coord(local), (1, 1));
+    let (tileset, local) = map.tileset_for(Gid(8)).unwrap();
+    assert_eq!((tileset.first_gid, local), (1, 7));
+
+    assert!(matches!(
+        parse(tmx),
+        Err(TiledError::MissingTileset(source)) if source == "sheet.tsx"
+    ));
+}
+
+#[test]
+fn tileset_tiles_keep_properties_and_animations() {
+    let tileset = Tileset::parse(
+        r#"<tileset name="sheet" columns="4">
+ <tile id="3" type="wall">
+  <properties>
+   <property name="collision" type="bool" value="true"/>
+  </properties>
+  <animation>
+   <frame tileid="3" duration="100"/>
+   <frame tileid="7" duration="250"/>
+  </animation>
+ </tile>
+</tileset>"#,
+    )
+    .unwrap();
+    let tile = &tileset.tiles[&3];
+    assert_eq!(tile.properties.bool("collision"), Some(true));
+    assert_eq!(
+        tile.animation,
+        [
+            Frame {
+                tile_id: 3,
+                duration: 100
+            },
+            Frame {
+                tile_id: 7,
+                duration: 250
+            }
+        ]
+    );
+    assert!(!tileset.tiles.contains_key(&0));
+}
+
+#[test]
+fn property_types_are_parsed() {
+    let map = parse(&map(r##"<layer id="1" name="floor" width="2" height="2">
+  <properties>
+   <property name="flag" type="bool" value="false"/>
+   <property name="count" type="int" value="-3"/>
+   <property name="target" type="object" value="12"/>
+   <property name="speed" type="float" value="1.5"/>
+   <property name="unset" type="float"/>
+   <property name="tint" type="color" value="#ff00ff00"/>
+   <property name="label" value="pantry"/>
+   <property name="note">multi
+line</property>
+  </properties>
+ </layer>"##))
+    .unwrap();
+    let properties = &map.layers[0].properties;
+    assert_eq!(properties.bool("flag"), Some(false));
+    assert_eq!(properties.get("count"), Some(&PropertyValue::Int(-3)));
+    assert_eq!(properties.get("target"), Some(&PropertyValue::Int(12)));
+    assert_eq!(properties.float("count"), Some(-3.0));
+    assert_eq!(properties.float("speed"), Some(1.5));
+    assert_eq!(properties.float("unset"), Some(0.0));
+    assert_eq!(
+        properties.get("tint"),
+        Some(&PropertyValue::String("#ff00ff00".into()))
+    );
+    assert_eq!(
+        properties.get("label"),
+        Some(&PropertyValue::String("pantry".into()))
+    );
+    assert_eq!(
+        properties.get("note"),
+        Some(&PropertyValue::String("multi\nline".into()))
+    );
+    assert_eq!(properties.float("label"), None);
+    assert_eq!(properties.bool("missing"), None);
+    // A layer without data is filled with empty tiles.
+    assert_eq!(map.layers[0].data, [Gid::EMPTY; 4]);
+
+    let mut merged = properties.clone();
+    let mut other = Properties::default();
+    other.0.insert("flag".into(), PropertyValue::Bool(true));
+    merged.merge(&other);
+    assert_eq!(merged.bool("flag"), Some(true));
+    assert_eq!(merged.float("speed"), Some(1.5));
+}
+
+#[test]
+fn objects_keep_their_shape_and_class() {
+    let map = parse(&map(r#"<objectgroup id="2" name="objects">
+  <object id="1" name="player_start" x="8" y="8"><point/></object>
+  <object id="2" name="kitchen" type="mouse_spawn" x="0" y="16" width="32" height="16"/>
+  <object id="3" class="exit" x="10" y="10"><polygon points="0,0 20,-5 5,15"/></object>
+  <object id="4" gid="1" x="0" y="32" width="16" height="16"/>
+ </objectgroup>
+ <group id="3" name="nested">
+  <objectgroup id="4" name="more">
+   <object id="5" x="1" y="2" width="3" height="4"><ellipse/></object>
+  </objectgroup>
+ </group>"#))
+    .unwrap();
+    let objects = &map.objects;
+    assert_eq!(objects.len(), 5);
+    assert_eq!(objects[0].shape, ObjectShape::Point);
+    assert_eq!(objects[0].kind(), "player_start");
+    assert_eq!(objects[0].bounds(), (8.0, 8.0, 0.0, 0.0));
+    assert_eq!(objects[1].kind(), "mouse_spawn");
+    assert_eq!(objects[1].bounds(), (0.0, 16.0, 32.0, 16.0));
+    assert_eq!(objects[2].kind(), "exit");
+    assert_eq!(objects[2].bounds(), (10.0, 5.0, 20.0, 20.0));
+    // Tile objects are moved from their bottom left anchor to the top left.
+    assert_eq!(objects[3].bounds(), (0.0, 16.0, 16.0, 16.0));
+    assert_eq!(objects[4].shape, ObjectShape::Ellipse);
+}
+
+#[test]
+fn unknown_encodings_and_compressions_are_rejected() {
+    assert!(matches!(
+        parse(&layer(("xml", None, ""))),
+        Err(TiledError::UnsupportedEncoding(encoding)) if encoding == "xml"
+    ));
+    assert!(matches!(
+        parse(&layer(("base64", Some("lzma"), BASE64.2))),
+        Err(TiledError::UnsupportedCompression(compression)) if compression == "lzma"
+    ));
+}
+
+#[test]
+fn bad_tile_data_is_rejected() {
+    assert!(matches!(
+        parse(&layer(("csv", None, "1,2,x,4"))),
+        Err(TiledError::InvalidTileData(gid)) if gid == "x"
+    ));
+    assert!(matches!(
+        parse(&layer(("csv", None, "1,4294967296,3,4"))),
+        Err(TiledError::InvalidTileData(gid)) if gid == "4294967296"
+    ));
+    assert!(matches!(
+        parse(&layer(("base64", None, "AQAAAAIA"))),
+        Err(TiledError::InvalidTileData(_))
+    ));
+    assert!(matches!(
+        parse(&layer(("base64", None, "not base64!"))),
+        Err(TiledError::Base64(_))
+    ));
+    assert!(matches!(
+        parse(&layer(("base64", Some("zlib"), BASE64.2))),
+        Err(TiledError::Decompress(_))
+    ));
+    assert!(matches!(
+        parse(&layer(("csv", None, "1,2,3"))),
+        Err(TiledError::DataLength {
+            expected: 4,
+            found: 3,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn gids_below_every_tileset_are_unknown() {
+    let tmx = r#"<map orientation="orthogonal" width="1" height="1">
+ <tileset firstgid="5" columns="4"/>
+</map>"#;
+    let map = parse(tmx).unwrap();
+    assert!(matches!(
+        map.tileset_for(Gid(0x8000_0003)),
+        Err(TiledError::UnknownGid(3))
+    ));
+    assert!(map.tileset_for(Gid(5)).is_ok());
+}
+
+#[test]
+fn malformed_documents_are_rejected() {
+    assert!(matches!(parse("<map"), Err(TiledError::Xml(_))));
+    assert!(matches!(
+        parse(TILESET),
+        Err(TiledError::UnexpectedRoot {
+            expected: "map",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse(r#"<map orientation="isometric" width="1" height="1"/>"#),
+        Err(TiledError::UnsupportedOrientation(orientation)) if orientation == "isometric"
+    ));
+    assert!(matches!(
+        parse(r#"<map width="1"/>"#),
+        Err(TiledError::MissingAttribute {
+            attribute: "height",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse(r#"<map width="wide" height="1"/>"#),
+        Err(TiledError::InvalidAttribute {
+            attribute: "width",
+            ..
+        })
+    ));
+}
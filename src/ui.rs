@@ -0,0 +1,182 @@
+//! Buttons that work with a mouse, a touch screen, keys and controllers. Screens add
+//! their buttons every frame between `Ui::begin` and the next `begin`, and the order they
+//! are added in identifies them.
+use crate::input::{Action, Controls};
+use macroquad::prelude::*;
+
+const FOCUS_COLOR: Color = Color::new(0.984, 0.949, 0.212, 1.0);
+
+#[derive(Default)]
+pub struct Ui {
+    /// Screen pixels per UI unit.
+    scale: f32,
+    /// The pointer in UI units.
+    mouse: Vec2,
+    mouse_moved: bool,
+    /// Where each button added since `begin` is, in UI units.
+    rects: Vec<Rect>,
+    focus: usize,
+    /// The button the pointer went down on, which activates if it is released on it too.
+    held: Option<usize>,
+    confirm: bool,
+}
+impl Ui {
+    /// Starts a frame of buttons laid out in units of `scale` pixels. `controls` moves
+    /// focus and activates the focused button, unless a widget has taken over the keys.
+    /// With `start`, a controller's Start button activates too, which it can't while it
+    /// pauses the round.
+    pub fn begin(&mut self, scale: f32, controls: Option<&Controls>, start: bool) {
+        let mouse = Vec2::from(mouse_position()) / scale;
+        self.mouse_moved = mouse != self.mouse;
+        self.mouse = mouse;
+        self.scale = scale;
+        // Kept through the frame the button comes up, so `add` can see the release.
+        if !is_mouse_button_down(MouseButton::Left) && !is_mouse_button_released(MouseButton::Left)
+        {
+            self.held = None;
+        }
+        let rects = std::mem::take(&mut self.rects);
+        self.confirm = false;
+        let Some(controls) = controls else {
+            return;
+        };
+        self.confirm = controls.is_pressed(Action::Confirm)
+            || (start && controls.gamepads.is_pressed(Action::Pause));
+        if rects.is_empty() {
+            return;
+        }
+        self.focus = self.focus.min(rects.len() - 1);
+        if is_key_pressed(KeyCode::Tab) {
+            self.focus = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                (self.focus + rects.len() - 1) % rects.len()
+            } else {
+                (self.focus + 1) % rects.len()
+            };
+        }
+        let directions = [
+            (Action::MoveUp, vec2(0.0, -1.0)),
+            (Action::MoveDown, vec2(0.0, 1.0)),
+            (Action::MoveLeft, vec2(-1.0, 0.0)),
+            (Action::MoveRight, vec2(1.0, 0.0)),
+        ];
+        for (action, direction) in directions {
+            if controls.is_pressed(action)
+                && let Some(next) = Self::neighbour(&rects, self.focus, direction)
+            {
+                self.focus = next;
+            }
+        }
+    }
+    /// Moves focus to the first button, for when a screen changes.
+    pub fn focus_first(&mut self) {
+        self.focus = 0;
+    }
+    /// The closest button from `from` in `direction`, preferring ones straight ahead.
+    fn neighbour(rects: &[Rect], from: usize, direction: Vec2) -> Option<usize> {
+        let origin = rects[from].center();
+        rects
+            .iter()
+            .enumerate()
+            .filter(|f| f.0 != from)
+            .map(|(index, rect)| {
+                let offset = rect.center() - origin;
+                let ahead = offset.dot(direction);
+                let aside = offset.perp_dot(direction).abs();
+                (index, ahead, ahead + aside * 2.0)
+            })
+            .filter(|f| f.1 > 0.0)
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|f| f.0)
+    }
+    /// Adds a button at `rect` and returns its state, plus whether it was activated.
+    fn add(&mut self, rect: Rect) -> (ButtonState, bool) {
+        let id = self.rects.len();
+        self.rects.push(rect);
+        let hovered = rect.contains(self.mouse);
+        if hovered && self.mouse_moved {
+            self.focus = id;
+        }
+        if hovered && is_mouse_button_pressed(MouseButton::Left) {
+            self.held = Some(id);
+            self.focus = id;
+        }
+        let held = self.held == Some(id);
+        let released = held && hovered && is_mouse_button_released(MouseButton::Left);
+        let focused = self.focus == id;
+        let state = ButtonState {
+            hovered,
+            pressed: held && hovered,
+            focused,
+        };
+        (state, released || (focused && self.confirm))
+    }
+    /// A line of text with its baseline at `pos`. Returns whether it was activated.
+    pub fn text_button(&mut self, text: &str, pos: Vec2, font_size: f32) -> bool {
+        let width = measure_text(text, None, (font_size * self.scale) as u16, 1.0).width;
+        let rect = Rect::new(pos.x, pos.y - font_size, width / self.scale, font_size);
+        let (state, activated) = self.add(rect);
+        let color = if state.pressed {
+            GRAY
+        } else if state.focused {
+            FOCUS_COLOR
+        } else {
+            WHITE
+        };
+        let offset = if state.pressed { 1.0 } else { 0.0 };
+        draw_text(
+            text,
+            pos.x * self.scale,
+            (pos.y + offset) * self.scale,
+            font_size * self.scale,
+            color,
+        );
+        if state.hovered {
+            draw_line(
+                pos.x * self.scale,
+                (pos.y + offset + 1.0) * self.scale,
+                (pos.x + rect.w) * self.scale,
+                (pos.y + offset + 1.0) * self.scale,
+                self.scale.max(1.0),
+                color,
+            );
+        }
+        activated
+    }
+    /// A texture stretched over `rect`. Returns whether it was activated.
+    pub fn image_button(&mut self, texture: &Texture2D, rect: Rect) -> bool {
+        let (state, activated) = self.add(rect);
+        let offset = if state.pressed { 1.0 } else { 0.0 };
+        draw_texture_ex(
+            texture,
+            rect.x * self.scale,
+            (rect.y + offset) * self.scale,
+            if state.pressed {
+                GRAY
+            } else if state.hovered {
+                LIGHTGRAY
+            } else {
+                WHITE
+            },
+            DrawTextureParams {
+                dest_size: Some(rect.size() * self.scale),
+                ..Default::default()
+            },
+        );
+        if state.focused {
+            draw_rectangle_lines(
+                (rect.x - 1.0) * self.scale,
+                (rect.y + offset - 1.0) * self.scale,
+                (rect.w + 2.0) * self.scale,
+                (rect.h + 2.0) * self.scale,
+                self.scale.max(2.0),
+                FOCUS_COLOR,
+            );
+        }
+        activated
+    }
+}
+struct ButtonState {
+    hovered: bool,
+    pressed: bool,
+    focused: bool,
+}